@@ -27,22 +27,28 @@ use std::collections::HashMap;
 use std::io::Cursor;
 
 use avro_rs::types::Value;
-use avro_rs::{from_avro_datum, Schema};
-use futures::future::{BoxFuture, Shared};
+use avro_rs::Schema;
+use futures::future::{self, join_all, BoxFuture, Shared};
+use futures::stream::{self, BoxStream, Stream, StreamExt};
 use futures::FutureExt;
+use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use serde_json::value;
 
 use crate::async_impl::schema_registry::{
-    get_referenced_schema, get_schema_by_id_and_type, get_schema_by_subject, SrSettings,
+    get_latest_schema_if_changed, get_referenced_schema, get_schema_by_id_and_type,
+    get_schema_by_subject, SrSettings,
 };
 use crate::avro_common::{
-    get_name, item_to_bytes, replace_reference, values_to_bytes, AvroSchema, DecodeResult,
+    check_uncompressed, content_hash, describe_fields, frame_at, item_to_bytes, read_value,
+    read_value_with_reader, replace_reference, value_into, values_to_bytes, AvroSchema,
+    DecodeResult,
 };
 use crate::error::SRCError;
 use crate::schema_registry_common::{
-    get_bytes_result, get_subject, BytesResult, RegisteredReference, RegisteredSchema, SchemaType,
-    SubjectNameStrategy,
+    extend_reference_path, get_bytes_result, get_id_and_data, get_schema, get_subject, BatchResult,
+    BytesResult, CacheCounters, CacheStats, DescribedValue, LatestSchema, PayloadCompression,
+    RegisteredReference, RegisteredSchema, SchemaType, SubjectNameStrategy,
 };
 
 /// A decoder used to transform bytes to a Value object
@@ -82,6 +88,8 @@ use crate::schema_registry_common::{
 pub struct AvroDecoder<'a> {
     sr_settings: SrSettings,
     cache: HashMap<u32, Shared<BoxFuture<'a, Result<AvroSchema, SRCError>>>, RandomState>,
+    counters: CacheCounters,
+    compression: PayloadCompression,
 }
 
 impl<'a> AvroDecoder<'a> {
@@ -94,8 +102,15 @@ impl<'a> AvroDecoder<'a> {
         AvroDecoder {
             sr_settings,
             cache: HashMap::new(),
+            counters: CacheCounters::default(),
+            compression: PayloadCompression::None,
         }
     }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<AvroDecoder<'a>, SRCError> {
+        SrSettings::global().map(AvroDecoder::new)
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
@@ -120,7 +135,7 @@ impl<'a> AvroDecoder<'a> {
     ///
     /// let heartbeat = decoder.decode(Some(&bytes)).await;
     ///
-    /// assert_eq!(heartbeat, Err(SRCError::new("Could not get raw schema from response", None, false).into_cache()));
+    /// assert_eq!(heartbeat, Err(SRCError::schema_not_found(2).into_cache()));
     ///
     /// let _m = mock("GET", "/schemas/ids/2?deleted=true")
     ///     .with_status(200)
@@ -129,7 +144,7 @@ impl<'a> AvroDecoder<'a> {
     ///     .create();
     ///
     /// let heartbeat = decoder.decode(Some(&bytes)).await;
-    /// assert_eq!(heartbeat, Err(SRCError::new("Could not get raw schema from response", None, false).into_cache()));
+    /// assert_eq!(heartbeat, Err(SRCError::schema_not_found(2).into_cache()));
     ///
     /// decoder.remove_errors_from_cache();
     ///
@@ -139,10 +154,22 @@ impl<'a> AvroDecoder<'a> {
     /// # }
     /// ```
     pub fn remove_errors_from_cache(&mut self) {
+        let before = self.cache.len();
         self.cache.retain(|_, v| match v.peek() {
             Some(r) => r.is_ok(),
             None => true,
         });
+        self.counters.evicted(before - self.cache.len());
+    }
+    /// Gives a snapshot of the statistics of the cache, like the number of entries, and how often a
+    /// schema could be taken from the cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.counters.to_stats(self.cache.len())
+    }
+    /// Sets the compression of the bytes after the header, which will be decompressed before
+    /// decoding. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
     }
     /// Decodes bytes into a value.
     /// The choice to use Option<&[u8]> as type us made so it plays nice with the BorrowedMessage
@@ -170,34 +197,212 @@ impl<'a> AvroDecoder<'a> {
                 name: None,
                 value: Value::Null,
             }),
-            BytesResult::Valid(id, bytes) => self.deserialize(id, &bytes).await,
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?)
+                    .await
+            }
             BytesResult::Invalid(bytes) => Err(SRCError::non_retryable_without_cause(&*format!(
                 "Invalid bytes {:?}",
                 bytes
             ))),
         }
     }
+    /// Decodes bytes like decode, and maps the value to the type, which should match the schema.
+    /// When the bytes are null `None` is returned. When the id in the bytes is of another type of
+    /// schema, a non-retryable error is returned.
+    pub async fn decode_into<T: DeserializeOwned>(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<Option<T>, SRCError> {
+        if let BytesResult::Null = get_bytes_result(bytes) {
+            return Ok(None);
+        }
+        let value = self.decode(bytes).await?.value;
+        value_into(&value).map(Some)
+    }
+    /// Decodes bytes like decode, and also gives the schema id from the bytes, for example to log
+    /// which schema was used. Null bytes, or bytes without valid framing, give an error.
+    pub async fn decode_with_id(&mut self, bytes: Option<&[u8]>) -> Result<(u32, Value), SRCError> {
+        let (id, data) = get_id_and_data(bytes)?;
+        let value = self
+            .deserialize(id, &self.compression.decompress(data)?)
+            .await?
+            .value;
+        Ok((id, value))
+    }
+    /// Decodes bytes like decode, and also gives a hash of the decoded content. The hash is the same
+    /// across runs and platforms, so it can be used to detect duplicate messages.
+    pub async fn decode_and_hash(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<(Value, u64), SRCError> {
+        let value = self.decode(bytes).await?.value;
+        let hash = content_hash(&value);
+        Ok((value, hash))
+    }
+    /// Decodes the bytes with the latest schema of the subject as reader schema, the schema of the id
+    /// in the bytes is used as writer schema. This is meant to check whether data can be read with
+    /// the latest schema, for normal decoding use decode. The latest schema is not cached.
+    pub async fn decode_with_subject_latest(
+        &mut self,
+        subject_name_strategy: &SubjectNameStrategy,
+        bytes: Option<&[u8]>,
+    ) -> Result<DecodeResult, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(DecodeResult {
+                name: None,
+                value: Value::Null,
+            }),
+            BytesResult::Valid(id, bytes) => {
+                let writer_schema = self.get_schema(id).clone().await?;
+                let registered_schema =
+                    get_schema_by_subject(&self.sr_settings, subject_name_strategy).await?;
+                let reader_schema = to_avro_schema(&self.sr_settings, registered_schema).await?;
+                read_value_with_reader(
+                    &writer_schema,
+                    &reader_schema,
+                    &self.compression.decompress(bytes)?,
+                )
+            }
+            BytesResult::Invalid(bytes) => Err(SRCError::non_retryable_without_cause(&format!(
+                "Invalid bytes {:?}",
+                bytes
+            ))),
+        }
+    }
+    /// Decodes bytes like decode, but also gives the declared types of the fields of the record, as
+    /// derived from the schema.
+    pub async fn decode_with_field_types(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<DescribedValue<DecodeResult>, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(DescribedValue {
+                value: DecodeResult {
+                    name: None,
+                    value: Value::Null,
+                },
+                fields: Vec::new(),
+            }),
+            BytesResult::Valid(id, bytes) => {
+                let schema = self.get_schema(id).clone().await?;
+                let value = read_value(
+                    &schema,
+                    &mut Cursor::new(&self.compression.decompress(bytes)?[..]),
+                )?;
+                Ok(DescribedValue {
+                    value,
+                    fields: describe_fields(&schema.parsed),
+                })
+            }
+            BytesResult::Invalid(bytes) => Err(SRCError::non_retryable_without_cause(&format!(
+                "Invalid bytes {:?}",
+                bytes
+            ))),
+        }
+    }
+    /// Decodes a batch of records, only the records with valid framing are decoded, null and invalid
+    /// records are returned as skipped, so they can be handled separately. The batch is aborted
+    /// when one of the valid records can't be decoded.
+    pub async fn decode_batch(
+        &mut self,
+        items: &[Option<&[u8]>],
+    ) -> Result<BatchResult<DecodeResult>, SRCError> {
+        let mut result = BatchResult {
+            decoded: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for (index, bytes) in items.iter().enumerate() {
+            match get_bytes_result(*bytes) {
+                BytesResult::Valid(id, bytes) => {
+                    let value = self
+                        .deserialize(id, &self.compression.decompress(bytes)?)
+                        .await?;
+                    result.decoded.push((index, value))
+                }
+                skipped => result.skipped.push((index, skipped)),
+            }
+        }
+        Ok(result)
+    }
+    /// Decodes bytes containing multiple concatenated messages, each with its own magic byte and
+    /// schema id, into values in the same order. Avro doesn't need the length of a value, so the
+    /// next message is assumed to start right after the end of the previous value. Bytes which don't
+    /// form a complete message, including a trailing part, give an error. Can't be combined with a
+    /// compressed payload, since then the end of the value isn't known.
+    pub async fn decode_all(&mut self, bytes: &[u8]) -> Result<Vec<DecodeResult>, SRCError> {
+        check_uncompressed(self.compression)?;
+        let mut results = Vec::new();
+        let mut position = 0;
+        while position < bytes.len() {
+            let (id, payload) = frame_at(bytes, position)?;
+            let schema = self.get_schema(id).clone().await?;
+            let mut reader = Cursor::new(&payload[..]);
+            results.push(read_value(&schema, &mut reader)?);
+            position += 5 + reader.position() as usize;
+        }
+        Ok(results)
+    }
+    /// Decodes each item of the stream, in the same order, using the same cache as decode. Items are
+    /// only taken from the supplied stream when the resulting stream is polled, so nothing is
+    /// buffered.
+    ///
+    /// ```
+    /// use mockito::{mock, server_address};
+    /// use avro_rs::types::Value;
+    /// use futures::stream::{self, StreamExt};
+    /// use schema_registry_converter::async_impl::schema_registry::SrSettings;
+    /// use schema_registry_converter::async_impl::avro::AvroDecoder;
+    ///
+    /// # async fn doc() -> Result<(), reqwest::Error> {
+    /// let _m = mock("GET", "/schemas/ids/1?deleted=true")
+    ///     .with_status(200)
+    ///     .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+    ///     .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+    ///     .create();
+    ///
+    /// let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+    /// let mut decoder = AvroDecoder::new(sr_settings);
+    /// let input = stream::iter(vec![Some(vec![0, 0, 0, 0, 1, 6]), None]);
+    /// let values: Vec<Value> = decoder
+    ///     .decode_stream(input)
+    ///     .map(|r| r.unwrap().value)
+    ///     .collect()
+    ///     .await;
+    ///
+    /// assert_eq!(values, vec![Value::Record(vec![("beat".to_string(), Value::Long(3))]), Value::Null]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn decode_stream<'s, S>(
+        &'s mut self,
+        input: S,
+    ) -> BoxStream<'s, Result<DecodeResult, SRCError>>
+    where
+        S: Stream<Item = Option<Vec<u8>>> + Send + 's,
+    {
+        stream::unfold((self, Box::pin(input)), |(decoder, mut input)| async move {
+            let item = input.next().await?;
+            let result = decoder.decode(item.as_deref()).await;
+            Some((result, (decoder, input)))
+        })
+        .boxed()
+    }
     /// The actual deserialization trying to get the id from the bytes to retrieve the schema, and
     /// using a reader transforms the bytes to a value.
     async fn deserialize(&mut self, id: u32, bytes: &[u8]) -> Result<DecodeResult, SRCError> {
         let schema = self.get_schema(id).clone().await?;
-        let mut reader = Cursor::new(bytes);
-        match from_avro_datum(&schema.parsed, &mut reader, None) {
-            Ok(v) => Ok(DecodeResult {
-                name: get_name(&schema.parsed),
-                value: v,
-            }),
-            Err(e) => Err(SRCError::non_retryable_with_cause(
-                e,
-                "Could not transform bytes using schema",
-            )),
-        }
+        read_value(&schema, &mut Cursor::new(bytes))
     }
 
     fn get_schema(&mut self, id: u32) -> &Shared<BoxFuture<'a, Result<AvroSchema, SRCError>>> {
         match self.cache.entry(id) {
-            Entry::Occupied(e) => &*e.into_mut(),
+            Entry::Occupied(e) => {
+                self.counters.hit();
+                &*e.into_mut()
+            }
             Entry::Vacant(e) => {
+                self.counters.miss();
                 let sr_settings = self.sr_settings.clone();
                 let v = async move {
                     match get_schema_by_id_and_type(id, &sr_settings, SchemaType::Avro).await {
@@ -213,6 +418,19 @@ impl<'a> AvroDecoder<'a> {
             }
         }
     }
+    #[cfg(all(feature = "json", feature = "proto_decoder"))]
+    /// Puts a schema that was already fetched, for example to detect the schema type, in the cache,
+    /// so it's not fetched again when decoding.
+    pub(crate) fn add_to_cache(&mut self, id: u32, registered_schema: RegisteredSchema) {
+        if let Entry::Vacant(e) = self.cache.entry(id) {
+            self.counters.miss();
+            let sr_settings = self.sr_settings.clone();
+            let v = async move { to_avro_schema(&sr_settings, registered_schema).await }
+                .boxed()
+                .shared();
+            e.insert(v);
+        }
+    }
 }
 
 /// An encoder used to transform a Value object to bytes
@@ -266,6 +484,7 @@ impl<'a> AvroDecoder<'a> {
 pub struct AvroEncoder<'a> {
     sr_settings: SrSettings,
     cache: HashMap<String, Shared<BoxFuture<'a, Result<AvroSchema, SRCError>>>, RandomState>,
+    compression: PayloadCompression,
 }
 
 impl<'a> AvroEncoder<'a> {
@@ -309,8 +528,14 @@ impl<'a> AvroEncoder<'a> {
         AvroEncoder {
             sr_settings,
             cache: HashMap::new(),
+            compression: PayloadCompression::None,
         }
     }
+    /// Sets the compression of the bytes after the header, the header itself is not compressed. The
+    /// decoder needs to use the same compression. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
@@ -335,7 +560,7 @@ impl<'a> AvroEncoder<'a> {
     ///     .create();
     ///
     /// let bytes = encoder.encode(vec![("beat", Value::Long(3))], strategy.clone()).await;
-    /// assert_eq!(bytes, Err(SRCError::new("Could not get id from response", None, false).into_cache()));
+    /// assert_eq!(bytes, Err(SRCError::new("schema registry responded with error code 40403", Some(String::from("Schema not found")), false).with_status(404).with_registry_error(40403, "Schema not found").into_cache()));
     ///
     /// let _m = mock("GET", "/subjects/nl.openweb.data.Heartbeat/versions/latest")
     ///     .with_status(200)
@@ -344,7 +569,7 @@ impl<'a> AvroEncoder<'a> {
     ///     .create();
     ///
     /// let bytes = encoder.encode(vec![("beat", Value::Long(3))], strategy.clone()).await;
-    /// assert_eq!(bytes, Err(SRCError::new("Could not get id from response", None, false).into_cache()));
+    /// assert_eq!(bytes, Err(SRCError::new("schema registry responded with error code 40403", Some(String::from("Schema not found")), false).with_status(404).with_registry_error(40403, "Schema not found").into_cache()));
     ///
     /// encoder.remove_errors_from_cache();
     ///
@@ -359,6 +584,15 @@ impl<'a> AvroEncoder<'a> {
             None => true,
         });
     }
+    /// Waits until all the schema's in the cache are fetched or registered, and returns the first
+    /// error, if any. Can be used on shutdown so no registration is left unfinished.
+    pub async fn flush(&self) -> Result<(), SRCError> {
+        let pending: Vec<_> = self.cache.values().cloned().collect();
+        for result in join_all(pending).await {
+            result?;
+        }
+        Ok(())
+    }
     /// Encodes a vector of values to bytes. The correct values of the 'keys' depend on the schema
     /// being fetched at runtime, or the one supplied with the SubjectNameStrategy.
     ///
@@ -397,7 +631,8 @@ impl<'a> AvroEncoder<'a> {
             .get_schema_and_id(key, subject_name_strategy)
             .clone()
             .await?;
-        values_to_bytes(&schema, values)
+        self.compression
+            .compress_payload(values_to_bytes(&schema, values)?)
     }
 
     /// Encodes a struct or a primitive value to bytes. The schema used for the encoding will be
@@ -458,7 +693,8 @@ impl<'a> AvroEncoder<'a> {
             .get_schema_and_id(key, subject_name_strategy.clone())
             .clone()
             .await?;
-        item_to_bytes(&schema, item)
+        self.compression
+            .compress_payload(item_to_bytes(&schema, item)?)
     }
 
     fn get_schema_and_id(
@@ -471,9 +707,14 @@ impl<'a> AvroEncoder<'a> {
             Entry::Vacant(e) => {
                 let sr_settings = self.sr_settings.clone();
                 let v = async move {
-                    match get_schema_by_subject(&sr_settings, &subject_name_strategy).await {
-                        Ok(registered_schema) => {
-                            to_avro_schema(&sr_settings, registered_schema).await
+                    match get_schema_for_encoding(&sr_settings, &subject_name_strategy).await {
+                        Ok((registered_schema, etag)) => {
+                            to_avro_schema(&sr_settings, registered_schema).await.map(
+                                |avro_schema| AvroSchema {
+                                    etag,
+                                    ..avro_schema
+                                },
+                            )
                         }
                         Err(e) => Err(e.into_cache()),
                     }
@@ -484,6 +725,57 @@ impl<'a> AvroEncoder<'a> {
             }
         }
     }
+    /// Checks whether the cached latest schema's of subjects are still the latest, by sending the
+    /// ETag they were fetched with. Unchanged schema's are not downloaded again, changed ones are
+    /// replaced so they are used for the next records. This only has effect when the schema
+    /// registry sends ETags, schema's supplied with the subject name strategy are never refreshed.
+    pub async fn refresh_latest(&mut self) -> Result<(), SRCError> {
+        let cached: Vec<(String, String)> = self
+            .cache
+            .iter()
+            .filter_map(|(subject, v)| match v.peek() {
+                Some(Ok(AvroSchema {
+                    etag: Some(etag), ..
+                })) => Some((subject.clone(), etag.clone())),
+                _ => None,
+            })
+            .collect();
+        for (subject, etag) in cached {
+            if let LatestSchema::Modified { schema, etag } =
+                get_latest_schema_if_changed(&self.sr_settings, &subject, Some(&etag)).await?
+            {
+                let v = to_avro_schema(&self.sr_settings, schema)
+                    .await
+                    .map(|avro_schema| AvroSchema {
+                        etag,
+                        ..avro_schema
+                    });
+                self.cache
+                    .insert(subject, future::ready(v).boxed().shared());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Gets the schema to encode with, for the latest schema of the subject together with the ETag of
+/// the response, so it can be refreshed cheaply.
+async fn get_schema_for_encoding(
+    sr_settings: &SrSettings,
+    subject_name_strategy: &SubjectNameStrategy,
+) -> Result<(RegisteredSchema, Option<String>), SRCError> {
+    if get_schema(subject_name_strategy).is_some() {
+        return get_schema_by_subject(sr_settings, subject_name_strategy)
+            .await
+            .map(|s| (s, None));
+    }
+    let subject = get_subject(subject_name_strategy)?;
+    match get_latest_schema_if_changed(sr_settings, &subject, None).await? {
+        LatestSchema::Modified { schema, etag } => Ok((schema, etag)),
+        LatestSchema::NotModified => Err(SRCError::non_retryable_without_cause(
+            "schema registry responded with not modified, while no etag was sent",
+        )),
+    }
 }
 
 async fn to_avro_schema(
@@ -501,7 +793,14 @@ async fn to_avro_schema(
     }
     let main_schema = match serde_json::from_str(&*registered_schema.schema) {
         Ok(v) => {
-            match add_references(sr_settings, v, registered_schema.references.as_slice()).await {
+            match add_references(
+                sr_settings,
+                v,
+                registered_schema.references.as_slice(),
+                vec![],
+            )
+            .await
+            {
                 Ok(u) => u,
                 Err(e) => return Err(e),
             }
@@ -518,6 +817,7 @@ async fn to_avro_schema(
             id: registered_schema.id,
             raw: registered_schema.schema,
             parsed,
+            etag: None,
         }),
         Err(e) => Err(SRCError::non_retryable_with_cause(
             e,
@@ -533,10 +833,13 @@ fn add_references<'a>(
     sr_settings: &'a SrSettings,
     json_value: value::Value,
     references: &'a [RegisteredReference],
+    path: Vec<String>,
 ) -> BoxFuture<'a, Result<value::Value, SRCError>> {
     async move {
         let mut new_value = json_value;
         for r in references.iter() {
+            let child_path =
+                extend_reference_path(&path, &r.subject, sr_settings.max_reference_depth())?;
             let registered_schema = match get_referenced_schema(sr_settings, r).await {
                 Ok(v) => v,
                 Err(e) => {
@@ -556,11 +859,17 @@ fn add_references<'a>(
                 }
             };
             new_value = replace_reference(new_value, child);
-            new_value =
-                match add_references(sr_settings, new_value, &registered_schema.references).await {
-                    Ok(v) => v,
-                    Err(e) => return Err(e),
-                }
+            new_value = match add_references(
+                sr_settings,
+                new_value,
+                &registered_schema.references,
+                child_path,
+            )
+            .await
+            {
+                Ok(v) => v,
+                Err(e) => return Err(e),
+            }
         }
         Ok(new_value)
     }
@@ -570,10 +879,10 @@ fn add_references<'a>(
 #[cfg(test)]
 mod tests {
     use avro_rs::from_value;
-    use mockito::{mock, server_address};
+    use mockito::{mock, server_address, Matcher};
 
     use crate::avro_common::get_supplied_schema;
-    use crate::schema_registry_common::SuppliedSchema;
+    use crate::schema_registry_common::{FieldDescriptor, SuppliedSchema};
 
     use super::*;
     use test_utils::Heartbeat;
@@ -583,7 +892,7 @@ mod tests {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let decoder = AvroDecoder::new(sr_settings);
         assert_eq!(
-            "AvroDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client { accepts: Accepts, proxies: [Proxy(System({}), None)], referer: true, default_headers: {\"accept\": \"*/*\"} }, authorization: None }, cache: {} }"
+            "AvroDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client { accepts: Accepts, proxies: [Proxy(System({}), None)], referer: true, default_headers: {\"accept\": \"*/*\"} }, authorization: None, max_reference_depth: 64, content_types: {}, deadline: None, retry_statuses: {}, read_only: false, query_params: [], schema_transform: None, retry_policy: None, register_only_new_versions: false }, cache: {}, counters: CacheCounters { hits: 0, misses: 0, evictions: 0 }, compression: None }"
                 .to_owned(),
             format!("{:?}", decoder)
         )
@@ -617,6 +926,91 @@ mod tests {
         assert_eq!(item.beat, 3i64);
     }
 
+    #[tokio::test]
+    async fn test_decode_with_id() {
+        let _m = mock("GET", "/schemas/ids/1?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let (id, value) = decoder
+            .decode_with_id(Some(&[0, 0, 0, 0, 1, 6]))
+            .await
+            .unwrap();
+        let error = decoder.decode_with_id(None).await.unwrap_err();
+
+        assert_eq!(id, 1);
+        assert_eq!(
+            value,
+            Value::Record(vec![("beat".to_string(), Value::Long(3))])
+        );
+        assert_eq!(error.error, "no schema id, because the bytes are null")
+    }
+
+    #[tokio::test]
+    async fn test_decode_into() {
+        let _m = mock("GET", "/schemas/ids/96?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+        let _n = mock("GET", "/schemas/ids/97?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schemaType":"PROTOBUF","schema":"syntax = \"proto3\";"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let heartbeat: Option<Heartbeat> = decoder
+            .decode_into(Some(&[0, 0, 0, 0, 96, 6]))
+            .await
+            .unwrap();
+        let none: Option<Heartbeat> = decoder.decode_into(None).await.unwrap();
+        let error = decoder
+            .decode_into::<Heartbeat>(Some(&[0, 0, 0, 0, 97, 6]))
+            .await
+            .unwrap_err();
+
+        assert_eq!(heartbeat.unwrap().beat, 3i64);
+        assert!(none.is_none());
+        assert!(!error.retriable)
+    }
+
+    #[tokio::test]
+    async fn test_decode_stream() {
+        let _m = mock("GET", "/schemas/ids/1?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let input = stream::iter(vec![
+            Some(vec![0, 0, 0, 0, 1, 6]),
+            Some(vec![1]),
+            Some(vec![0, 0, 0, 0, 1, 8]),
+        ]);
+        let results: Vec<Result<DecodeResult, SRCError>> =
+            decoder.decode_stream(input).collect().await;
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].as_ref().unwrap().value,
+            Value::Record(vec![("beat".to_string(), Value::Long(3))])
+        );
+        assert!(results[1].is_err());
+        assert_eq!(
+            results[2].as_ref().unwrap().value,
+            Value::Record(vec![("beat".to_string(), Value::Long(4))])
+        );
+        assert_eq!(decoder.cache_stats().misses, 1)
+    }
+
     #[tokio::test]
     async fn test_decoder_with_name() {
         let _m = mock("GET", "/schemas/ids/1?deleted=true")
@@ -791,6 +1185,180 @@ mod tests {
         assert_eq!(err.error, "http call to schema registry failed")
     }
 
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_decoder_gzip_compressed() {
+        use std::io::Write;
+        let _m = mock("GET", "/schemas/ids/1?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+
+        let mut encoder = libflate::gzip::Encoder::new(Vec::new()).unwrap();
+        encoder.write_all(&[6]).unwrap();
+        let bytes =
+            crate::schema_registry_common::get_payload(1, encoder.finish().into_result().unwrap());
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        decoder.set_compression(PayloadCompression::Gzip);
+        let heartbeat = decoder.decode(Some(&bytes)).await.unwrap().value;
+
+        assert_eq!(
+            heartbeat,
+            Value::Record(vec![("beat".to_string(), Value::Long(3))])
+        )
+    }
+
+    #[cfg(feature = "deflate")]
+    #[tokio::test]
+    async fn test_encode_decode_deflate_compressed() {
+        let _m_latest = mock(
+            "GET",
+            "/subjects/heartbeat-nl.openweb.data.Heartbeat/versions/latest",
+        )
+        .with_status(200)
+        .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+        .with_body(r#"{"subject":"heartbeat-value","version":1,"id":3,"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+        .create();
+        let _m_id = mock("GET", "/schemas/ids/3?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut encoder = AvroEncoder::new(sr_settings.clone());
+        encoder.set_compression(PayloadCompression::Deflate);
+        let strategy = SubjectNameStrategy::TopicRecordNameStrategy(
+            String::from("heartbeat"),
+            String::from("nl.openweb.data.Heartbeat"),
+        );
+        let bytes = encoder
+            .encode(vec![("beat", Value::Long(3))], strategy)
+            .await
+            .unwrap();
+
+        assert_eq!(bytes[..5], [0, 0, 0, 0, 3]);
+        assert_ne!(bytes[5..], [6]);
+
+        let mut decoder = AvroDecoder::new(sr_settings);
+        decoder.set_compression(PayloadCompression::Deflate);
+        let heartbeat = decoder.decode(Some(&bytes)).await.unwrap().value;
+
+        assert_eq!(
+            heartbeat,
+            Value::Record(vec![("beat".to_string(), Value::Long(3))])
+        )
+    }
+
+    #[tokio::test]
+    async fn test_decode_all() {
+        let _m = mock("GET", "/schemas/ids/1?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let heartbeats = decoder
+            .decode_all(&[0, 0, 0, 0, 1, 6, 0, 0, 0, 0, 1, 8])
+            .await
+            .unwrap();
+
+        let values: Vec<Value> = heartbeats.into_iter().map(|r| r.value).collect();
+        assert_eq!(
+            values,
+            vec![
+                Value::Record(vec![("beat".to_string(), Value::Long(3))]),
+                Value::Record(vec![("beat".to_string(), Value::Long(4))])
+            ]
+        );
+        let err = decoder
+            .decode_all(&[0, 0, 0, 0, 1, 6, 0, 0, 0])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.error,
+            "Bytes from position 6 don't form a complete message"
+        )
+    }
+
+    #[tokio::test]
+    async fn test_decode_with_field_types() {
+        let _m = mock("GET", "/schemas/ids/1?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"},{\"name\":\"note\",\"type\":[\"null\",\"string\"]}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let described = decoder
+            .decode_with_field_types(Some(&[0, 0, 0, 0, 1, 6, 0]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            described.fields,
+            vec![
+                FieldDescriptor {
+                    name: String::from("beat"),
+                    type_name: String::from("long"),
+                    nullable: false,
+                    repeated: false,
+                },
+                FieldDescriptor {
+                    name: String::from("note"),
+                    type_name: String::from("string"),
+                    nullable: true,
+                    repeated: false,
+                },
+            ]
+        );
+        assert_eq!(
+            described.value.value,
+            Value::Record(vec![
+                ("beat".to_string(), Value::Long(3)),
+                ("note".to_string(), Value::Union(Box::new(Value::Null)))
+            ])
+        )
+    }
+
+    #[tokio::test]
+    async fn test_decode_batch() {
+        let _m = mock("GET", "/schemas/ids/1?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let valid: &[u8] = &[0, 0, 0, 0, 1, 6];
+        let invalid: &[u8] = &[1, 2];
+        let batch = decoder
+            .decode_batch(&[Some(valid), None, Some(invalid)])
+            .await
+            .unwrap();
+
+        assert_eq!(batch.decoded.len(), 1);
+        assert_eq!(batch.decoded[0].0, 0);
+        assert_eq!(
+            batch.decoded[0].1.value,
+            Value::Record(vec![("beat".to_string(), Value::Long(3))])
+        );
+        assert_eq!(
+            batch.skipped,
+            vec![
+                (1, BytesResult::Null),
+                (2, BytesResult::Invalid(vec![1, 2]))
+            ]
+        )
+    }
+
     #[tokio::test]
     async fn test_decoder_default_no_schema_in_response() {
         let _m = mock("GET", "/schemas/ids/1?deleted=true")
@@ -864,6 +1432,46 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_decode_with_subject_latest() {
+        let _m = mock("GET", "/schemas/ids/91?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+        let _n = mock("GET", "/subjects/reader-heartbeat-value/versions/latest")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"subject":"reader-heartbeat-value","version":2,"id":92,"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"},{\"name\":\"source\",\"type\":\"string\",\"default\":\"unknown\"}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let strategy =
+            SubjectNameStrategy::TopicNameStrategy(String::from("reader-heartbeat"), false);
+
+        let result = decoder
+            .decode_with_subject_latest(&strategy, Some(&[0, 0, 0, 0, 91, 6]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.value,
+            Value::Record(vec![
+                ("beat".to_string(), Value::Long(3)),
+                ("source".to_string(), Value::String("unknown".to_string()))
+            ])
+        );
+        assert_eq!(
+            decoder
+                .decode(Some(&[0, 0, 0, 0, 91, 6]))
+                .await
+                .unwrap()
+                .value,
+            Value::Record(vec![("beat".to_string(), Value::Long(3))])
+        )
+    }
+
     #[tokio::test]
     async fn test_decoder_cache() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
@@ -876,10 +1484,7 @@ mod tests {
             .with_body(r#"{"error_code":40403,"message":"Schema not found"}"#)
             .create();
         let err = decoder.decode(Some(&bytes)).await.unwrap_err();
-        assert_eq!(
-            err,
-            SRCError::new("Could not get raw schema from response", None, false).into_cache()
-        );
+        assert_eq!(err, SRCError::schema_not_found(2).into_cache());
         let _m = mock("GET", "/schemas/ids/2?deleted=true")
             .with_status(200)
             .with_header("content-type", "application/vnd.schemaregistry.v1+json")
@@ -887,10 +1492,7 @@ mod tests {
             .create();
 
         let err = decoder.decode(Some(&bytes)).await.unwrap_err();
-        assert_eq!(
-            err,
-            SRCError::new("Could not get raw schema from response", None, false).into_cache()
-        );
+        assert_eq!(err, SRCError::schema_not_found(2).into_cache());
 
         decoder.remove_errors_from_cache();
 
@@ -901,12 +1503,45 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn test_decoder_cache_stats() {
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let bytes = [0, 0, 0, 0, 2, 6];
+
+        let _m = mock("GET", "/schemas/ids/2?deleted=true")
+            .with_status(404)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"error_code":40403,"message":"Schema not found"}"#)
+            .create();
+        decoder.decode(Some(&bytes)).await.unwrap_err();
+        decoder.remove_errors_from_cache();
+
+        let _m = mock("GET", "/schemas/ids/2?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+        decoder.decode(Some(&bytes)).await.unwrap();
+        decoder.decode(Some(&bytes)).await.unwrap();
+
+        assert_eq!(
+            decoder.cache_stats(),
+            CacheStats {
+                entries: 1,
+                hits: 1,
+                misses: 2,
+                evictions: 1,
+            }
+        )
+    }
+
     #[test]
     fn display_encode() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let encoder = AvroEncoder::new(sr_settings);
         assert_eq!(
-            "AvroEncoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client { accepts: Accepts, proxies: [Proxy(System({}), None)], referer: true, default_headers: {\"accept\": \"*/*\"} }, authorization: None }, cache: {} }"
+            "AvroEncoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client { accepts: Accepts, proxies: [Proxy(System({}), None)], referer: true, default_headers: {\"accept\": \"*/*\"} }, authorization: None, max_reference_depth: 64, content_types: {}, deadline: None, retry_statuses: {}, read_only: false, query_params: [], schema_transform: None, retry_policy: None, register_only_new_versions: false }, cache: {}, compression: None }"
                 .to_owned(),
             format!("{:?}", encoder)
         )
@@ -939,6 +1574,53 @@ mod tests {
         assert_eq!(bytes, vec![0, 0, 0, 0, 3, 6])
     }
 
+    #[tokio::test]
+    async fn test_refresh_latest() {
+        let _m = mock("GET", "/subjects/refresh-value/versions/latest")
+            .match_header("if-none-match", Matcher::Missing)
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_header("etag", "\"v1\"")
+            .with_body(r#"{"subject":"refresh-value","version":1,"id":3,"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .expect(1)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut encoder = AvroEncoder::new(sr_settings);
+        let strategy = SubjectNameStrategy::TopicNameStrategy(String::from("refresh"), false);
+        let bytes = encoder
+            .encode(vec![("beat", Value::Long(3))], strategy.clone())
+            .await;
+        assert_eq!(bytes, Ok(vec![0, 0, 0, 0, 3, 6]));
+
+        let not_modified = mock("GET", "/subjects/refresh-value/versions/latest")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(304)
+            .expect(1)
+            .create();
+        assert_eq!(encoder.refresh_latest().await, Ok(()));
+        not_modified.assert();
+        drop(not_modified);
+        let bytes = encoder
+            .encode(vec![("beat", Value::Long(3))], strategy.clone())
+            .await;
+        assert_eq!(bytes, Ok(vec![0, 0, 0, 0, 3, 6]));
+
+        let _n = mock("GET", "/subjects/refresh-value/versions/latest")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_header("etag", "\"v2\"")
+            .with_body(r#"{"subject":"refresh-value","version":3,"id":5,"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+        assert_eq!(encoder.refresh_latest().await, Ok(()));
+        let bytes = encoder
+            .encode(vec![("beat", Value::Long(3))], strategy.clone())
+            .await;
+        assert_eq!(bytes, Ok(vec![0, 0, 0, 0, 5, 6]));
+        _m.assert();
+    }
+
     #[tokio::test]
     async fn test_using_record_name() {
         let _m = mock("GET", "/subjects/heartbeat-nl.openweb.data.Heartbeat/versions/latest")
@@ -1067,7 +1749,14 @@ mod tests {
             .unwrap_err();
         assert_eq!(
             err,
-            SRCError::new("Could not get id from response", None, false).into_cache()
+            SRCError::new(
+                "schema registry responded with error code 40403",
+                Some(String::from("Schema not found")),
+                false,
+            )
+            .with_status(404)
+            .with_registry_error(40403, "Schema not found")
+            .into_cache()
         );
 
         let _n = mock("GET", "/subjects/nl.openweb.data.Heartbeat/versions/latest")
@@ -1082,7 +1771,14 @@ mod tests {
             .unwrap_err();
         assert_eq!(
             err,
-            SRCError::non_retryable_without_cause("Could not get id from response").into_cache()
+            SRCError::new(
+                "schema registry responded with error code 40403",
+                Some(String::from("Schema not found")),
+                false,
+            )
+            .with_status(404)
+            .with_registry_error(40403, "Schema not found")
+            .into_cache()
         );
 
         encoder.remove_errors_from_cache();
@@ -1094,6 +1790,37 @@ mod tests {
         assert_eq!(bytes, vec![0, 0, 0, 0, 4, 6])
     }
 
+    #[tokio::test]
+    async fn test_encoder_flush() {
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut encoder = AvroEncoder::new(sr_settings);
+        assert_eq!(encoder.flush().await, Ok(()));
+
+        let _m = mock("GET", "/subjects/nl.openweb.data.Heartbeat/versions/latest")
+            .with_status(404)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"error_code":40403,"message":"Schema not found"}"#)
+            .create();
+
+        let strategy =
+            SubjectNameStrategy::RecordNameStrategy(String::from("nl.openweb.data.Heartbeat"));
+        let _ = encoder
+            .encode(vec![("beat", Value::Long(3))], strategy)
+            .await;
+
+        assert_eq!(
+            encoder.flush().await,
+            Err(SRCError::new(
+                "schema registry responded with error code 40403",
+                Some(String::from("Schema not found")),
+                false,
+            )
+            .with_status(404)
+            .with_registry_error(40403, "Schema not found")
+            .into_cache())
+        )
+    }
+
     #[tokio::test]
     async fn test_encode_key_and_value_supplied_record() {
         let _n = mock("POST", "/subjects/heartbeat-key/versions")
@@ -1264,14 +1991,9 @@ mod tests {
             .unwrap_err();
         assert_eq!(
             error,
-            SRCError::new(
-                "could not parse to RawRegisteredSchema, schema might not exist on this schema registry, the http call failed, cause will give more information",
-                Some(String::from(
-                    "error decoding response body: EOF while parsing a value at line 1 column 0"
-                )),
-                false,
-            )
-            .into_cache()
+            SRCError::new("schema registry responded with status 501", None, false)
+                .with_status(501)
+                .into_cache()
         )
     }
 
@@ -1279,6 +2001,7 @@ mod tests {
     async fn error_when_invalid_schema() {
         let registered_schema = RegisteredSchema {
             id: 0,
+            version: None,
             schema_type: SchemaType::Avro,
             schema: String::from(r#"{"type":"record","name":"Name"}"#),
             references: vec![],
@@ -1297,6 +2020,7 @@ mod tests {
     async fn error_when_invalid_type() {
         let registered_schema = RegisteredSchema {
             id: 0,
+            version: None,
             schema_type: SchemaType::Protobuf,
             schema: String::from(
                 r#"syntax = "proto3"; package org.schema_registry_test_app.proto; message Result { string up = 1; string down = 2; }"#,
@@ -1341,6 +2065,37 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_encode_struct_and_decode_into() {
+        let schema = r#"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"#;
+        let _m = mock("GET", "/subjects/round-trip-value/versions/latest")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(format!(
+                r#"{{"subject":"round-trip-value","version":1,"id":98,"schema":"{}"}}"#,
+                schema
+            ))
+            .create();
+        let _n = mock("GET", "/schemas/ids/98?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(format!(r#"{{"schema":"{}"}}"#, schema))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut encoder = AvroEncoder::new(sr_settings.clone());
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let strategy = SubjectNameStrategy::TopicNameStrategy(String::from("round-trip"), false);
+
+        let bytes = encoder
+            .encode_struct(Heartbeat { beat: 42 }, &strategy)
+            .await
+            .unwrap();
+        let heartbeat: Heartbeat = decoder.decode_into(Some(&bytes)).await.unwrap().unwrap();
+
+        assert_eq!(heartbeat.beat, 42)
+    }
+
     #[tokio::test]
     async fn test_primitive_schema_incompatible_strategy() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
@@ -0,0 +1,129 @@
+//! Wrappers around the Avro decoder and encoder, which can be cloned and shared between tasks
+//! without wrapping them yourself. The wrapped decoder or encoder is locked for the duration of a
+//! call, so concurrent calls are handled one at a time, sharing the same cache.
+use std::sync::Arc;
+
+use avro_rs::types::Value;
+use serde::ser::Serialize;
+use tokio::sync::Mutex;
+
+use crate::async_impl::avro::{AvroDecoder, AvroEncoder};
+use crate::async_impl::schema_registry::SrSettings;
+use crate::avro_common::DecodeResult;
+use crate::error::SRCError;
+use crate::schema_registry_common::SubjectNameStrategy;
+
+/// A decoder used to transform bytes to a Value object, which can be cloned, with all the clones
+/// sharing the same decoder.
+#[derive(Clone, Debug)]
+pub struct EasyAvroDecoder {
+    decoder: Arc<Mutex<AvroDecoder<'static>>>,
+}
+
+impl EasyAvroDecoder {
+    pub fn new(sr_settings: SrSettings) -> EasyAvroDecoder {
+        EasyAvroDecoder {
+            decoder: Arc::new(Mutex::new(AvroDecoder::new(sr_settings))),
+        }
+    }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<EasyAvroDecoder, SRCError> {
+        SrSettings::global().map(EasyAvroDecoder::new)
+    }
+    /// Decodes the bytes like `AvroDecoder::decode`.
+    pub async fn decode(&self, bytes: Option<&[u8]>) -> Result<DecodeResult, SRCError> {
+        self.decoder.lock().await.decode(bytes).await
+    }
+}
+
+/// An encoder used to transform a Value object to bytes, which can be cloned, with all the clones
+/// sharing the same encoder.
+#[derive(Clone, Debug)]
+pub struct EasyAvroEncoder {
+    encoder: Arc<Mutex<AvroEncoder<'static>>>,
+}
+
+impl EasyAvroEncoder {
+    pub fn new(sr_settings: SrSettings) -> EasyAvroEncoder {
+        EasyAvroEncoder {
+            encoder: Arc::new(Mutex::new(AvroEncoder::new(sr_settings))),
+        }
+    }
+    /// Encodes the values like `AvroEncoder::encode`.
+    pub async fn encode(
+        &self,
+        values: Vec<(&'static str, Value)>,
+        subject_name_strategy: SubjectNameStrategy,
+    ) -> Result<Vec<u8>, SRCError> {
+        self.encoder
+            .lock()
+            .await
+            .encode(values, subject_name_strategy)
+            .await
+    }
+    /// Encodes the item like `AvroEncoder::encode_struct`.
+    pub async fn encode_struct(
+        &self,
+        item: impl Serialize,
+        subject_name_strategy: &SubjectNameStrategy,
+    ) -> Result<Vec<u8>, SRCError> {
+        self.encoder
+            .lock()
+            .await
+            .encode_struct(item, subject_name_strategy)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use avro_rs::types::Value;
+    use mockito::{mock, server_address};
+
+    use crate::async_impl::easy_avro::{EasyAvroDecoder, EasyAvroEncoder};
+    use crate::async_impl::schema_registry::SrSettings;
+    use crate::schema_registry_common::SubjectNameStrategy;
+
+    #[tokio::test]
+    async fn shared_between_tasks() {
+        let schema = r#"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"#;
+        let _m = mock("GET", "/subjects/easy-value/versions/latest")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(format!(
+                r#"{{"subject":"easy-value","version":1,"id":99,"schema":"{}"}}"#,
+                schema
+            ))
+            .create();
+        let _n = mock("GET", "/schemas/ids/99?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(format!(r#"{{"schema":"{}"}}"#, schema))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let encoder = EasyAvroEncoder::new(sr_settings.clone());
+        let decoder = EasyAvroDecoder::new(sr_settings);
+        let mut handles = Vec::new();
+        for beat in 0..4i64 {
+            let encoder = encoder.clone();
+            let decoder = decoder.clone();
+            handles.push(tokio::spawn(async move {
+                let strategy = SubjectNameStrategy::TopicNameStrategy(String::from("easy"), false);
+                let bytes = encoder
+                    .encode(vec![("beat", Value::Long(beat))], strategy)
+                    .await
+                    .unwrap();
+                decoder.decode(Some(&bytes)).await.unwrap().value
+            }));
+        }
+
+        for (beat, handle) in handles.into_iter().enumerate() {
+            assert_eq!(
+                handle.await.unwrap(),
+                Value::Record(vec![("beat".to_string(), Value::Long(beat as i64))])
+            )
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::collections::hash_map::{Entry, RandomState};
 use std::collections::HashMap;
 use std::str::FromStr;
 
-use futures::future::{BoxFuture, FutureExt, Shared};
+use futures::future::{join_all, BoxFuture, FutureExt, Shared};
 use futures::stream::{self, StreamExt};
 use serde_json::Value;
 use url::Url;
@@ -13,10 +13,13 @@ use crate::async_impl::schema_registry::{
     get_referenced_schema, get_schema_by_id_and_type, get_schema_by_subject, SrSettings,
 };
 use crate::error::SRCError;
-use crate::json_common::{fetch_fallback, fetch_id, handle_validation, to_bytes, to_value};
+use crate::json_common::{
+    content_hash, fetch_fallback, fetch_id, handle_validation, to_bytes, to_value,
+};
 use crate::schema_registry_common::{
-    get_bytes_result, get_subject, BytesResult, RegisteredReference, RegisteredSchema, SchemaType,
-    SubjectNameStrategy,
+    extend_reference_path, get_bytes_result, get_id_and_data, get_subject, BytesResult,
+    CacheCounters, CacheStats, PayloadCompression, RegisteredReference, RegisteredSchema,
+    SchemaType, SubjectNameStrategy,
 };
 
 /// Encoder that works by prepending the correct bytes in order to make it valid schema registry
@@ -27,6 +30,7 @@ use crate::schema_registry_common::{
 pub struct JsonEncoder<'a> {
     sr_settings: SrSettings,
     cache: HashMap<String, Shared<BoxFuture<'a, Result<JsonSchema, SRCError>>>, RandomState>,
+    validate_on_encode: bool,
 }
 
 impl<'a> JsonEncoder<'a> {
@@ -35,8 +39,15 @@ impl<'a> JsonEncoder<'a> {
         JsonEncoder {
             sr_settings,
             cache: HashMap::new(),
+            validate_on_encode: true,
         }
     }
+    /// Sets whether the value is validated against the schema before encoding, when the value isn't
+    /// valid a non-retryable error with the validation errors is returned. By default values are
+    /// validated.
+    pub fn set_validate_on_encode(&mut self, validate_on_encode: bool) {
+        self.validate_on_encode = validate_on_encode;
+    }
     /// Removes errors from the cache, can be usefull to retry failed encodings.
     pub fn remove_errors_from_cache(&mut self) {
         self.cache.retain(|_, v| match v.peek() {
@@ -44,6 +55,15 @@ impl<'a> JsonEncoder<'a> {
             None => true,
         });
     }
+    /// Waits until all the schema's in the cache are fetched or registered, and returns the first
+    /// error, if any. Can be used on shutdown so no registration is left unfinished.
+    pub async fn flush(&self) -> Result<(), SRCError> {
+        let pending: Vec<_> = self.cache.values().cloned().collect();
+        for result in join_all(pending).await {
+            result?;
+        }
+        Ok(())
+    }
     /// Encodes the bytes by adding a few bytes to the message with additional information. The full
     /// names is the optional package followed with the message name, and optionally inner messages.
     pub async fn encode(
@@ -54,7 +74,9 @@ impl<'a> JsonEncoder<'a> {
         let key = get_subject(&subject_name_strategy)?;
         let schema = self.get_schema(key, subject_name_strategy).clone().await?;
         let id = schema.id;
-        validate(schema, value)?;
+        if self.validate_on_encode {
+            validate(schema, value)?;
+        }
         to_bytes(id, value)
     }
 
@@ -69,7 +91,7 @@ impl<'a> JsonEncoder<'a> {
                 let sr_settings = self.sr_settings.clone();
                 let v = async move {
                     match get_schema_by_subject(&sr_settings, &subject_name_strategy).await {
-                        Ok(schema) => to_json_schema(&sr_settings, None, schema).await,
+                        Ok(schema) => to_json_schema(&sr_settings, None, schema, vec![]).await,
                         Err(e) => Err(e.into_cache()),
                     }
                 }
@@ -111,10 +133,16 @@ pub struct JsonSchema {
     pub references: Vec<JsonSchema>,
 }
 
+/// Decodes bytes into a serde_json Value. By default numbers are parsed into either an i64, u64 or
+/// f64, so large decimals lose precision. With the `json_arbitrary_precision` feature numbers keep
+/// the exact textual representation, which is also used when encoding the value again.
 #[derive(Debug)]
 pub struct JsonDecoder<'a> {
     sr_settings: SrSettings,
     cache: HashMap<u32, Shared<BoxFuture<'a, Result<JsonSchema, SRCError>>>, RandomState>,
+    counters: CacheCounters,
+    compression: PayloadCompression,
+    validate_on_decode: bool,
 }
 
 impl<'a> JsonDecoder<'a> {
@@ -127,34 +155,90 @@ impl<'a> JsonDecoder<'a> {
         JsonDecoder {
             sr_settings,
             cache: HashMap::new(),
+            counters: CacheCounters::default(),
+            compression: PayloadCompression::None,
+            validate_on_decode: false,
         }
     }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<JsonDecoder<'a>, SRCError> {
+        SrSettings::global().map(JsonDecoder::new)
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
     pub fn remove_errors_from_cache(&mut self) {
+        let before = self.cache.len();
         self.cache.retain(|_, v| match v.peek() {
             Some(r) => r.is_ok(),
             None => true,
         });
+        self.counters.evicted(before - self.cache.len());
+    }
+    /// Gives a snapshot of the statistics of the cache, like the number of entries, and how often a
+    /// schema could be taken from the cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.counters.to_stats(self.cache.len())
+    }
+    /// Sets the compression of the bytes after the header, which will be decompressed before
+    /// decoding. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
+    }
+    /// Sets whether the decoded value is validated against the schema, when the value isn't valid a
+    /// non-retryable error with the validation errors is returned. By default values are not
+    /// validated.
+    pub fn set_validate_on_decode(&mut self, validate_on_decode: bool) {
+        self.validate_on_decode = validate_on_decode;
     }
     /// Reads the bytes to get the name, and gives back the data bytes.
     pub async fn decode(&mut self, bytes: Option<&[u8]>) -> Result<Option<DecodeResult>, SRCError> {
         match get_bytes_result(bytes) {
             BytesResult::Null => Ok(None),
-            BytesResult::Valid(id, bytes) => Ok(Some(self.deserialize(id, &bytes).await?)),
+            BytesResult::Valid(id, bytes) => Ok(Some(
+                self.deserialize(id, &self.compression.decompress(bytes)?)
+                    .await?,
+            )),
             BytesResult::Invalid(i) => Err(SRCError::non_retryable_without_cause(&*format!(
                 "Invalid bytes: {:?}",
                 i
             ))),
         }
     }
+    /// Decodes bytes like decode, and also gives the schema id from the bytes, for example to log
+    /// which schema was used. Null bytes, or bytes without valid framing, give an error.
+    pub async fn decode_with_id(&mut self, bytes: Option<&[u8]>) -> Result<(u32, Value), SRCError> {
+        let (id, data) = get_id_and_data(bytes)?;
+        let value = self
+            .deserialize(id, &self.compression.decompress(data)?)
+            .await?
+            .value;
+        Ok((id, value))
+    }
+    /// Decodes bytes like decode, and also gives a hash of the decoded content. Since the keys are
+    /// hashed in sorted order, the hash doesn't depend on the order of the keys in the json, and
+    /// can be used to detect duplicate messages.
+    pub async fn decode_and_hash(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<Option<(Value, u64)>, SRCError> {
+        Ok(self.decode(bytes).await?.map(|result| {
+            let hash = content_hash(&result.value);
+            (result.value, hash)
+        }))
+    }
     /// The actual deserialization trying to get the id from the bytes to retrieve the schema, and
     /// using a reader transforms the bytes to a value.
     async fn deserialize(&mut self, id: u32, bytes: &[u8]) -> Result<DecodeResult, SRCError> {
         let schema = self.get_schema(id).clone().await?;
         match serde_json::from_slice(bytes) {
-            Ok(value) => Ok(DecodeResult { schema, value }),
+            Ok(value) => {
+                if self.validate_on_decode {
+                    validate(schema.clone(), &value)?;
+                }
+                Ok(DecodeResult { schema, value })
+            }
             Err(e) => Err(SRCError::non_retryable_with_cause(
                 e,
                 "could not create value from bytes",
@@ -165,12 +249,16 @@ impl<'a> JsonDecoder<'a> {
     /// it into the cache.
     fn get_schema(&mut self, id: u32) -> &Shared<BoxFuture<'a, Result<JsonSchema, SRCError>>> {
         match self.cache.entry(id) {
-            Entry::Occupied(e) => &*e.into_mut(),
+            Entry::Occupied(e) => {
+                self.counters.hit();
+                &*e.into_mut()
+            }
             Entry::Vacant(e) => {
+                self.counters.miss();
                 let sr_settings = self.sr_settings.clone();
                 let v = async move {
                     match get_schema_by_id_and_type(id, &sr_settings, SchemaType::Json).await {
-                        Ok(schema) => to_json_schema(&sr_settings, None, schema).await,
+                        Ok(schema) => to_json_schema(&sr_settings, None, schema, vec![]).await,
                         Err(e) => Err(e.into_cache()),
                     }
                 }
@@ -180,6 +268,20 @@ impl<'a> JsonDecoder<'a> {
             }
         }
     }
+    #[cfg(all(feature = "avro", feature = "proto_decoder"))]
+    /// Puts a schema that was already fetched, for example to detect the schema type, in the cache,
+    /// so it's not fetched again when decoding.
+    pub(crate) fn add_to_cache(&mut self, id: u32, registered_schema: RegisteredSchema) {
+        if let Entry::Vacant(e) = self.cache.entry(id) {
+            self.counters.miss();
+            let sr_settings = self.sr_settings.clone();
+            let v =
+                async move { to_json_schema(&sr_settings, None, registered_schema, vec![]).await }
+                    .boxed()
+                    .shared();
+            e.insert(v);
+        }
+    }
 }
 
 fn reference_url(rr: &RegisteredReference) -> Result<Url, SRCError> {
@@ -200,13 +302,22 @@ fn to_json_schema(
     sr_settings: &SrSettings,
     optional_url: Option<Url>,
     registered_schema: RegisteredSchema,
+    path: Vec<String>,
 ) -> BoxFuture<Result<JsonSchema, SRCError>> {
     async move {
         let refs: Result<Vec<JsonSchema>, SRCError> = stream::iter(registered_schema.references)
-            .then(|rr| async move {
-                let url = reference_url(&rr)?;
-                let rs = get_referenced_schema(sr_settings, &rr).await?;
-                to_json_schema(sr_settings, Some(url), rs).await
+            .then(|rr| {
+                let path = &path;
+                async move {
+                    let child_path = extend_reference_path(
+                        path,
+                        &rr.subject,
+                        sr_settings.max_reference_depth(),
+                    )?;
+                    let url = reference_url(&rr)?;
+                    let rs = get_referenced_schema(sr_settings, &rr).await?;
+                    to_json_schema(sr_settings, Some(url), rs, child_path).await
+                }
             })
             .collect::<Vec<_>>()
             .await
@@ -319,6 +430,126 @@ mod tests {
         assert_eq!(true, encoded_data.is_ok());
     }
 
+    #[cfg(feature = "json_arbitrary_precision")]
+    #[tokio::test]
+    async fn test_decoder_keeps_number_precision() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_json_body(
+                r#"{\"type\":\"object\",\"properties\":{\"amount\":{\"type\":\"number\"}}}"#,
+                7,
+            ))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = JsonDecoder::new(sr_settings);
+        let bytes = get_payload(
+            7,
+            r#"{"amount":12345678901234567890.123456789012345678}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        let message = decoder.decode(Some(&bytes)).await.unwrap().unwrap();
+
+        assert_eq!(
+            "12345678901234567890.123456789012345678",
+            message.value["amount"].to_string()
+        );
+        assert_eq!(
+            bytes,
+            crate::json_common::to_bytes(7, &message.value).unwrap()
+        )
+    }
+
+    #[tokio::test]
+    async fn test_decode_with_id() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_json_body(json_result_schema(), 7))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = JsonDecoder::new(sr_settings);
+        let (id, value) = decoder
+            .decode_with_id(Some(&get_payload(
+                7,
+                br#"{"up":"STRING","down":"string"}"#.to_vec(),
+            )))
+            .await
+            .unwrap();
+
+        assert_eq!(id, 7);
+        assert_eq!(value["up"], "STRING")
+    }
+
+    #[tokio::test]
+    async fn test_decode_with_validation() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_json_body(json_result_schema(), 7))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = JsonDecoder::new(sr_settings);
+        let invalid = get_payload(7, br#""Foo""#.to_vec());
+        assert!(decoder.decode(Some(&invalid)).await.is_ok());
+
+        decoder.set_validate_on_decode(true);
+        let valid = get_payload(7, br#"{"up":"STRING","down":"string"}"#.to_vec());
+        assert!(decoder.decode(Some(&valid)).await.is_ok());
+        let error = decoder.decode(Some(&invalid)).await.unwrap_err();
+        assert_eq!(
+            error.error,
+            String::from(
+                r#"Value "Foo" was not valid according to the schema because [WrongType { path: "", detail: "The value must be object" }]"#
+            )
+        );
+        assert!(!error.retriable)
+    }
+
+    #[tokio::test]
+    async fn test_decode_and_hash_ignores_key_order() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_json_body(json_result_schema(), 7))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = JsonDecoder::new(sr_settings);
+        let (first, first_hash) = decoder
+            .decode_and_hash(Some(&get_payload(
+                7,
+                br#"{"up":"STRING","down":"string"}"#.to_vec(),
+            )))
+            .await
+            .unwrap()
+            .unwrap();
+        let (_, second_hash) = decoder
+            .decode_and_hash(Some(&get_payload(
+                7,
+                br#"{"down":"string","up":"STRING"}"#.to_vec(),
+            )))
+            .await
+            .unwrap()
+            .unwrap();
+        let (_, other_hash) = decoder
+            .decode_and_hash(Some(&get_payload(
+                7,
+                br#"{"down":"STRING","up":"string"}"#.to_vec(),
+            )))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!("STRING", first["up"]);
+        assert_eq!(first_hash, second_hash);
+        assert_ne!(first_hash, other_hash)
+    }
+
     #[tokio::test]
     async fn test_decoder_default() {
         let result_value: String = read_to_string("tests/schema/result-example.json")
@@ -631,6 +862,25 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn test_encode_without_validation() {
+        let _m = mock("GET", "/subjects/testresult-value/versions/latest")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_json_body(json_result_schema(), 10))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut encoder = JsonEncoder::new(sr_settings);
+        encoder.set_validate_on_encode(false);
+        let strategy = SubjectNameStrategy::TopicNameStrategy(String::from("testresult"), false);
+        let result_example: Value = Value::String(String::from("Foo"));
+
+        let bytes = encoder.encode(&result_example, strategy).await.unwrap();
+
+        assert_eq!(bytes, get_payload(10, br#""Foo""#.to_vec()))
+    }
+
     #[tokio::test]
     async fn test_encode_not_valid() {
         let _m = mock("GET", "/subjects/testresult-value/versions/latest")
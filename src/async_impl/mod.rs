@@ -1,7 +1,11 @@
 #[cfg(feature = "avro")]
 pub mod avro;
+#[cfg(feature = "avro")]
+pub mod easy_avro;
 #[cfg(feature = "json")]
 pub mod json;
+#[cfg(all(feature = "avro", feature = "json", feature = "proto_decoder"))]
+pub mod multi_decoder;
 #[cfg(feature = "proto_decoder")]
 pub mod proto_decoder;
 #[cfg(feature = "proto_raw")]
@@ -1,5 +1,5 @@
 use std::collections::hash_map::{Entry, RandomState};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use bytes::Bytes;
 use futures::future::{BoxFuture, Shared};
@@ -7,11 +7,19 @@ use futures::FutureExt;
 use protofish::{Context, MessageValue, Value};
 
 use crate::async_impl::schema_registry::{
-    get_referenced_schema, get_schema_by_id_and_type, SrSettings,
+    get_referenced_schema, get_schema_by_id_and_type, get_schema_by_subject, SrSettings,
 };
 use crate::error::SRCError;
+use crate::proto_decoder_common::{
+    content_hash, convert_well_known_types, describe_message, remove_corrupt_fields,
+    take_unknown_fields, to_decoded_value, ConvertedValue, DecodedValue, UnknownField,
+};
 use crate::proto_resolver::{resolve_name, to_index_and_data, MessageResolver};
-use crate::schema_registry_common::{get_bytes_result, BytesResult, RegisteredSchema, SchemaType};
+use crate::schema_registry_common::{
+    extend_reference_path, get_bytes_result, get_id_and_data, get_sliced_bytes_result, BatchResult,
+    BytesResult, CacheCounters, CacheStats, DescribedValue, PayloadCompression, RegisteredSchema,
+    SchemaType, SlicedBytesResult, SubjectNameStrategy,
+};
 
 type SharedFutureOfSchemas<'a> = Shared<BoxFuture<'a, Result<Vec<String>, SRCError>>>;
 
@@ -19,6 +27,9 @@ type SharedFutureOfSchemas<'a> = Shared<BoxFuture<'a, Result<Vec<String>, SRCErr
 pub struct ProtoDecoder<'a> {
     sr_settings: SrSettings,
     cache: HashMap<u32, SharedFutureOfSchemas<'a>, RandomState>,
+    counters: CacheCounters,
+    compression: PayloadCompression,
+    preloaded_references: HashMap<String, String>,
 }
 
 impl<'a> ProtoDecoder<'a> {
@@ -31,16 +42,43 @@ impl<'a> ProtoDecoder<'a> {
         ProtoDecoder {
             sr_settings,
             cache: HashMap::new(),
+            counters: CacheCounters::default(),
+            compression: PayloadCompression::None,
+            preloaded_references: HashMap::new(),
         }
     }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<ProtoDecoder<'a>, SRCError> {
+        SrSettings::global().map(ProtoDecoder::new)
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
     pub fn remove_errors_from_cache(&mut self) {
+        let before = self.cache.len();
         self.cache.retain(|_, v| match v.peek() {
             Some(r) => r.is_ok(),
             None => true,
         });
+        self.counters.evicted(before - self.cache.len());
+    }
+    /// Gives a snapshot of the statistics of the cache, like the number of entries, and how often a
+    /// schema could be taken from the cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.counters.to_stats(self.cache.len())
+    }
+    /// Sets the compression of the bytes after the header, which will be decompressed before
+    /// decoding. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
+    }
+    /// Sets schema's, keyed by the name of the reference, like the import path, which will be used
+    /// for references instead of fetching them from the schema registry. The supplied schema's
+    /// can't have references themselves. Should be set before decoding, since already resolved
+    /// schema's are cached.
+    pub fn set_preloaded_references(&mut self, preloaded_references: HashMap<String, String>) {
+        self.preloaded_references = preloaded_references;
     }
     /// Decodes bytes into a value.
     /// The choice to use Option<&[u8]> as type us made so it plays nice with the BorrowedMessage
@@ -50,32 +88,275 @@ impl<'a> ProtoDecoder<'a> {
     pub async fn decode(&mut self, bytes: Option<&[u8]>) -> Result<Value, SRCError> {
         match get_bytes_result(bytes) {
             BytesResult::Null => Ok(Value::Bytes(Bytes::new())),
-            BytesResult::Valid(id, bytes) => Ok(Value::Message(Box::from(
-                self.deserialize(id, &bytes).await?,
-            ))),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, |_, m| {
+                    Value::Message(Box::from(m))
+                })
+                .await
+            }
+            BytesResult::Invalid(i) => Ok(Value::Bytes(Bytes::from(i))),
+        }
+    }
+    /// Decodes bytes like decode, but from `Bytes`, like they are held by some Kafka clients. The
+    /// message is sliced from the bytes instead of copied, unless the payload is compressed.
+    pub async fn decode_bytes(&mut self, bytes: Option<&Bytes>) -> Result<Value, SRCError> {
+        match get_sliced_bytes_result(bytes) {
+            SlicedBytesResult::Null => Ok(Value::Bytes(Bytes::new())),
+            SlicedBytesResult::Valid(id, data) => {
+                let data = if self.compression == PayloadCompression::None {
+                    data
+                } else {
+                    Bytes::from(self.compression.decompress(data.to_vec())?)
+                };
+                self.deserialize(id, &data, |_, m| Value::Message(Box::from(m)))
+                    .await
+            }
+            SlicedBytesResult::Invalid(i) => Ok(Value::Bytes(i)),
+        }
+    }
+    /// Decodes a batch of records, only the records with valid framing are decoded, null and invalid
+    /// records are returned as skipped, so they can be handled separately. The batch is aborted
+    /// when one of the valid records can't be decoded.
+    pub async fn decode_batch(
+        &mut self,
+        items: &[Option<&[u8]>],
+    ) -> Result<BatchResult<Value>, SRCError> {
+        let mut result = BatchResult {
+            decoded: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for (index, bytes) in items.iter().enumerate() {
+            match get_bytes_result(*bytes) {
+                BytesResult::Valid(id, bytes) => {
+                    let value = self
+                        .deserialize(id, &self.compression.decompress(bytes)?, |_, m| {
+                            Value::Message(Box::from(m))
+                        })
+                        .await?;
+                    result.decoded.push((index, value))
+                }
+                skipped => result.skipped.push((index, skipped)),
+            }
+        }
+        Ok(result)
+    }
+    /// Decodes bytes like decode, but converts the well-known types Timestamp, Duration and Struct
+    /// to more convenient types. Other well-known types are kept as message.
+    pub async fn decode_with_well_known_types(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<ConvertedValue, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(ConvertedValue::Other(Value::Bytes(Bytes::new()))),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(
+                    id,
+                    &self.compression.decompress(bytes)?,
+                    convert_well_known_types,
+                )
+                .await
+            }
+            BytesResult::Invalid(i) => Ok(ConvertedValue::Other(Value::Bytes(Bytes::from(i)))),
+        }
+    }
+    /// Decodes bytes like decode, but into a value owned by this crate, so protofish is not needed
+    /// to use the result. Messages become maps with the names of the fields as keys.
+    pub async fn decode_value(&mut self, bytes: Option<&[u8]>) -> Result<DecodedValue, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(DecodedValue::Null),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, to_decoded_value)
+                    .await
+            }
+            BytesResult::Invalid(i) => Ok(DecodedValue::Bytes(i)),
+        }
+    }
+    /// Decodes the bytes with the latest schema of the subject, instead of the schema of the id in
+    /// the bytes, the message index in the bytes is resolved against the latest schema. This is
+    /// meant to check whether data can be read with the latest schema, for normal decoding use
+    /// decode. The latest schema is not cached.
+    pub async fn decode_with_subject_latest(
+        &mut self,
+        subject_name_strategy: &SubjectNameStrategy,
+        bytes: Option<&[u8]>,
+    ) -> Result<Value, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(Value::Bytes(Bytes::new())),
+            BytesResult::Valid(_, bytes) => {
+                let registered_schema =
+                    get_schema_by_subject(&self.sr_settings, subject_name_strategy).await?;
+                if registered_schema.schema_type != SchemaType::Protobuf {
+                    return Err(SRCError::non_retryable_without_cause(&format!(
+                        "type {:?}, is not correct",
+                        registered_schema.schema_type
+                    )));
+                }
+                let vec_of_schemas = to_vec_of_schemas(
+                    &self.sr_settings,
+                    &self.preloaded_references,
+                    registered_schema,
+                )
+                .await?;
+                let context = into_decode_context(&vec_of_schemas)?;
+                let bytes = self.compression.decompress(bytes)?;
+                let (index, data) = to_index_and_data(&bytes);
+                let full_name = resolve_name(&context.resolver, &index)?;
+                let message_info = context.context.get_message(full_name).unwrap();
+                Ok(Value::Message(Box::from(
+                    message_info.decode(data, &context.context),
+                )))
+            }
             BytesResult::Invalid(i) => Ok(Value::Bytes(Bytes::from(i))),
         }
     }
+    /// Decodes bytes like decode, and also gives the schema id from the bytes, for example to log
+    /// which schema was used. Null bytes, or bytes without valid framing, give an error.
+    pub async fn decode_with_id(&mut self, bytes: Option<&[u8]>) -> Result<(u32, Value), SRCError> {
+        let (id, data) = get_id_and_data(bytes)?;
+        let value = self
+            .deserialize(id, &self.compression.decompress(data)?, |_, m| {
+                Value::Message(Box::from(m))
+            })
+            .await?;
+        Ok((id, value))
+    }
+    /// Decodes bytes like decode, but also gives the full name of the message, as selected by the
+    /// message indexes after the schema id. When the bytes are null or not framed, the name is empty
+    /// and the value is the same as for decode.
+    pub async fn decode_with_name(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<(String, Value), SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok((String::new(), Value::Bytes(Bytes::new()))),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, |context, m| {
+                    let name = context.resolve_message(m.msg_ref).full_name.clone();
+                    (name, Value::Message(Box::from(m)))
+                })
+                .await
+            }
+            BytesResult::Invalid(i) => Ok((String::new(), Value::Bytes(Bytes::from(i)))),
+        }
+    }
+    /// Decodes bytes like decode, but also gives the declared types of the fields of the message,
+    /// as derived from the proto schema.
+    pub async fn decode_with_field_types(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<DescribedValue<Value>, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(DescribedValue {
+                value: Value::Bytes(Bytes::new()),
+                fields: Vec::new(),
+            }),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, describe_message)
+                    .await
+            }
+            BytesResult::Invalid(i) => Ok(DescribedValue {
+                value: Value::Bytes(Bytes::from(i)),
+                fields: Vec::new(),
+            }),
+        }
+    }
+    /// Decodes bytes like decode, and also gives a hash of the decoded content. The hash is
+    /// computed over the decoded value, so it doesn't depend on the order of the fields in the
+    /// bytes, and can be used to detect duplicate messages.
+    pub async fn decode_and_hash(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<(Value, u64), SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok((
+                Value::Bytes(Bytes::new()),
+                content_hash(&DecodedValue::Null),
+            )),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, |context, m| {
+                    let hash = content_hash(&to_decoded_value(context, m.clone()));
+                    (Value::Message(Box::from(m)), hash)
+                })
+                .await
+            }
+            BytesResult::Invalid(i) => {
+                let hash = content_hash(&DecodedValue::Bytes(i.clone()));
+                Ok((Value::Bytes(Bytes::from(i)), hash))
+            }
+        }
+    }
+    /// Decodes bytes like decode, but leaves out the fields that couldn't be parsed, also from
+    /// nested messages. The numbers of those fields are returned together with the value, so as
+    /// much as possible of a partly corrupt message can be inspected. Since decode keeps corrupt
+    /// fields as incomplete values, this is opt-in.
+    pub async fn decode_skipping_corrupt_fields(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<(Value, Vec<u64>), SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok((Value::Bytes(Bytes::new()), Vec::new())),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, |_, mut m| {
+                    let corrupt = remove_corrupt_fields(&mut m);
+                    (Value::Message(Box::from(m)), corrupt)
+                })
+                .await
+            }
+            BytesResult::Invalid(i) => Ok((Value::Bytes(Bytes::from(i)), Vec::new())),
+        }
+    }
+    /// Decodes bytes like decode, but the fields with a number unknown to the schema, for example
+    /// added by a newer version of the schema, are taken out of the message and returned
+    /// separately. This way they can be logged or passed on instead of being overlooked.
+    pub async fn decode_with_unknown_fields(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<(Value, Vec<UnknownField>), SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok((Value::Bytes(Bytes::new()), Vec::new())),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, |_, mut m| {
+                    let unknown = take_unknown_fields(&mut m);
+                    (Value::Message(Box::from(m)), unknown)
+                })
+                .await
+            }
+            BytesResult::Invalid(i) => Ok((Value::Bytes(Bytes::from(i)), Vec::new())),
+        }
+    }
     /// The actual deserialization trying to get the id from the bytes to retrieve the schema, and
-    /// using a reader transforms the bytes to a value.
-    async fn deserialize(&mut self, id: u32, bytes: &[u8]) -> Result<MessageValue, SRCError> {
+    /// using a reader transforms the bytes to a value, which is mapped using the context.
+    async fn deserialize<T>(
+        &mut self,
+        id: u32,
+        bytes: &[u8],
+        map: fn(&Context, MessageValue) -> T,
+    ) -> Result<T, SRCError> {
         let vec_of_schemas = self.get_vec_of_schemas(id).clone().await?;
         let context = into_decode_context(&vec_of_schemas)?;
         let (index, data) = to_index_and_data(bytes);
         let full_name = resolve_name(&context.resolver, &index)?;
         let message_info = context.context.get_message(full_name).unwrap();
-        Ok(message_info.decode(&data, &context.context))
+        Ok(map(
+            &context.context,
+            message_info.decode(data, &context.context),
+        ))
     }
     /// Gets the Context object, either from the cache, or from the schema registry and then putting
     /// it into the cache.
     fn get_vec_of_schemas(&mut self, id: u32) -> &SharedFutureOfSchemas<'a> {
         match self.cache.entry(id) {
-            Entry::Occupied(e) => &*e.into_mut(),
+            Entry::Occupied(e) => {
+                self.counters.hit();
+                &*e.into_mut()
+            }
             Entry::Vacant(e) => {
+                self.counters.miss();
                 let sr_settings = self.sr_settings.clone();
+                let preloaded_references = self.preloaded_references.clone();
                 let v = async move {
                     match get_schema_by_id_and_type(id, &sr_settings, SchemaType::Protobuf).await {
-                        Ok(v) => to_vec_of_schemas(&sr_settings, v).await,
+                        Ok(v) => to_vec_of_schemas(&sr_settings, &preloaded_references, v).await,
                         Err(e) => Err(e.into_cache()),
                     }
                 }
@@ -85,17 +366,55 @@ impl<'a> ProtoDecoder<'a> {
             }
         }
     }
+    #[cfg(all(feature = "avro", feature = "json"))]
+    /// Puts a schema that was already fetched, for example to detect the schema type, in the cache,
+    /// so it's not fetched again when decoding.
+    pub(crate) fn add_to_cache(&mut self, id: u32, registered_schema: RegisteredSchema) {
+        if let Entry::Vacant(e) = self.cache.entry(id) {
+            self.counters.miss();
+            let sr_settings = self.sr_settings.clone();
+            let preloaded_references = self.preloaded_references.clone();
+            let v = async move {
+                to_vec_of_schemas(&sr_settings, &preloaded_references, registered_schema).await
+            }
+            .boxed()
+            .shared();
+            e.insert(v);
+        }
+    }
 }
 
+/// Adds the schema after all its references, a reference that was already included, for example
+/// because two schemas import the same file, is only added once.
 fn add_files<'a>(
     sr_settings: &'a SrSettings,
+    preloaded_references: &'a HashMap<String, String>,
     registered_schema: RegisteredSchema,
     files: &'a mut Vec<String>,
+    included: &'a mut HashSet<String>,
+    path: Vec<String>,
 ) -> BoxFuture<'a, Result<(), SRCError>> {
     async move {
         for r in registered_schema.references {
+            if !included.insert(r.name.clone()) {
+                continue;
+            }
+            if let Some(schema) = preloaded_references.get(&r.name) {
+                files.push(schema.clone());
+                continue;
+            }
+            let child_path =
+                extend_reference_path(&path, &r.subject, sr_settings.max_reference_depth())?;
             let child_schema = get_referenced_schema(sr_settings, &r).await?;
-            add_files(sr_settings, child_schema, files).await?;
+            add_files(
+                sr_settings,
+                preloaded_references,
+                child_schema,
+                files,
+                included,
+                child_path,
+            )
+            .await?;
         }
         files.push(registered_schema.schema);
         Ok(())
@@ -122,20 +441,34 @@ fn into_decode_context(vec_of_schemas: &[String]) -> Result<DecodeContext, SRCEr
 
 async fn to_vec_of_schemas(
     sr_settings: &SrSettings,
+    preloaded_references: &HashMap<String, String>,
     registered_schema: RegisteredSchema,
 ) -> Result<Vec<String>, SRCError> {
     let mut vec_of_schemas = Vec::new();
-    add_files(sr_settings, registered_schema, &mut vec_of_schemas).await?;
+    add_files(
+        sr_settings,
+        preloaded_references,
+        registered_schema,
+        &mut vec_of_schemas,
+        &mut HashSet::new(),
+        vec![],
+    )
+    .await?;
     Ok(vec_of_schemas)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
+    use bytes::Bytes;
     use mockito::{mock, server_address};
     use protofish::Value;
 
     use crate::async_impl::proto_decoder::ProtoDecoder;
     use crate::async_impl::schema_registry::SrSettings;
+    use crate::proto_decoder_common::DecodedValue;
+    use crate::schema_registry_common::FieldDescriptor;
     use test_utils::{
         get_proto_complex, get_proto_complex_proto_test_message, get_proto_complex_references,
         get_proto_hb_101, get_proto_hb_schema, get_proto_result,
@@ -175,6 +508,147 @@ mod tests {
         assert_eq!(Value::UInt64(101u64), message.fields[0].value)
     }
 
+    #[tokio::test]
+    async fn test_decode_with_field_types() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 1))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let described = decoder
+            .decode_with_field_types(Some(get_proto_hb_101()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            described.fields,
+            vec![FieldDescriptor {
+                name: String::from("beat"),
+                type_name: String::from("uint64"),
+                nullable: false,
+                repeated: false,
+            }]
+        );
+        match described.value {
+            Value::Message(x) => assert_eq!(Value::UInt64(101u64), x.fields[0].value),
+            v => panic!("Other value: {:?} than expected Message", v),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_and_hash() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 1))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let (heartbeat, hash) = decoder
+            .decode_and_hash(Some(get_proto_hb_101()))
+            .await
+            .unwrap();
+        let (_, other_hash) = decoder
+            .decode_and_hash(Some(&[0, 0, 0, 0, 7, 0, 8, 102]))
+            .await
+            .unwrap();
+
+        match heartbeat {
+            Value::Message(x) => assert_eq!(Value::UInt64(101u64), x.fields[0].value),
+            v => panic!("Other value: {:?} than expected Message", v),
+        }
+        assert_eq!(
+            hash,
+            decoder
+                .decode_and_hash(Some(get_proto_hb_101()))
+                .await
+                .unwrap()
+                .1
+        );
+        assert_ne!(hash, other_hash)
+    }
+
+    #[tokio::test]
+    async fn test_decode_skipping_corrupt_fields() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 1))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let (heartbeat, corrupt) = decoder
+            .decode_skipping_corrupt_fields(Some(&[0, 0, 0, 0, 7, 0, 8, 101, 23]))
+            .await
+            .unwrap();
+
+        let message = match heartbeat {
+            Value::Message(x) => *x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+        assert_eq!(message.fields.len(), 1);
+        assert_eq!(Value::UInt64(101u64), message.fields[0].value);
+        assert_eq!(corrupt, vec![2])
+    }
+
+    #[tokio::test]
+    async fn test_decode_with_unknown_fields() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 1))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let (heartbeat, unknown) = decoder
+            .decode_with_unknown_fields(Some(&[0, 0, 0, 0, 7, 0, 8, 101, 16, 5]))
+            .await
+            .unwrap();
+
+        let message = match heartbeat {
+            Value::Message(x) => *x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+        assert_eq!(message.fields.len(), 1);
+        assert_eq!(Value::UInt64(101u64), message.fields[0].value);
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].number, 2)
+    }
+
+    #[tokio::test]
+    async fn test_decode_bytes() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 1))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let heartbeat = decoder
+            .decode_bytes(Some(&Bytes::from(get_proto_hb_101().to_vec())))
+            .await
+            .unwrap();
+
+        let message = match heartbeat {
+            Value::Message(x) => *x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+        assert_eq!(Value::UInt64(101u64), message.fields[0].value);
+        assert_eq!(
+            decoder
+                .decode_bytes(Some(&Bytes::from_static(&[1, 2])))
+                .await,
+            Ok(Value::Bytes(Bytes::from_static(&[1, 2])))
+        )
+    }
+
     #[tokio::test]
     async fn test_decoder_cache() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
@@ -203,6 +677,57 @@ mod tests {
         assert_eq!(Value::UInt64(101u64), message.fields[0].value);
     }
 
+    #[tokio::test]
+    async fn test_decoder_diamond_references() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body_with_reference(
+                r#"syntax = \"proto3\"; import \"b.proto\"; import \"c.proto\"; package diamond; message Top {uint64 beat = 1; B b = 2; C c = 3;}"#,
+                7,
+                r#"{"name": "b.proto", "subject": "b.proto", "version": 1}, {"name": "c.proto", "subject": "c.proto", "version": 1}"#,
+            ))
+            .create();
+        let _m_b = mock("GET", "/subjects/b.proto/versions/1")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body_with_reference(
+                r#"syntax = \"proto3\"; import \"d.proto\"; package diamond; message B {D d = 1;}"#,
+                8,
+                r#"{"name": "d.proto", "subject": "d.proto", "version": 1}"#,
+            ))
+            .create();
+        let _m_c = mock("GET", "/subjects/c.proto/versions/1")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body_with_reference(
+                r#"syntax = \"proto3\"; import \"d.proto\"; package diamond; message C {D d = 1;}"#,
+                9,
+                r#"{"name": "d.proto", "subject": "d.proto", "version": 1}"#,
+            ))
+            .create();
+        let _m_d = mock("GET", "/subjects/d.proto/versions/1")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(
+                r#"syntax = \"proto3\"; package diamond; message D {string id = 1;}"#,
+                10,
+            ))
+            .expect(1)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let top = decoder.decode(Some(get_proto_hb_101())).await.unwrap();
+
+        let message = match top {
+            Value::Message(x) => *x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+        assert_eq!(Value::UInt64(101u64), message.fields[0].value);
+        _m_d.assert()
+    }
+
     #[tokio::test]
     async fn test_decoder_complex() {
         let _m = mock("GET", "/schemas/ids/6?deleted=true")
@@ -235,6 +760,80 @@ mod tests {
         assert_eq!(message.fields[1].value, Value::Int64(1))
     }
 
+    #[tokio::test]
+    async fn test_decode_with_name() {
+        let _m = mock("GET", "/schemas/ids/6?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body_with_reference(
+                get_proto_complex(),
+                2,
+                get_proto_complex_references(),
+            ))
+            .create();
+
+        let _m = mock("GET", "/subjects/result.proto/versions/1")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_result(), 1))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let (top_level, _) = decoder
+            .decode_with_name(Some(get_proto_complex_proto_test_message()))
+            .await
+            .unwrap();
+        let (nested, value) = decoder
+            .decode_with_name(Some(&[0, 0, 0, 0, 6, 4, 4, 0, 8, 1]))
+            .await
+            .unwrap();
+
+        assert_eq!(top_level, "org.schema_registry_test_app.proto.ProtoTest");
+        assert_eq!(nested, "org.schema_registry_test_app.proto.C.D");
+        match value {
+            Value::Message(m) => assert_eq!(m.fields[0].value, Value::Int64(1)),
+            v => panic!("Other value: {:?} than expected Message", v),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decoder_complex_to_decoded_value() {
+        let _m = mock("GET", "/schemas/ids/6?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body_with_reference(
+                get_proto_complex(),
+                2,
+                get_proto_complex_references(),
+            ))
+            .create();
+
+        let _m = mock("GET", "/subjects/result.proto/versions/1")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_result(), 1))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let proto_test = decoder
+            .decode_value(Some(get_proto_complex_proto_test_message()))
+            .await
+            .unwrap();
+
+        let map = match proto_test {
+            DecodedValue::Map(x) => x,
+            v => panic!("Other value: {:?} than expected Map", v),
+        };
+        assert_eq!(map["counter"], DecodedValue::Int(1));
+        assert_eq!(map["input"], DecodedValue::String(String::from("String")));
+        match &map["results"] {
+            DecodedValue::List(results) => assert_eq!(results.len(), 1),
+            v => panic!("Other value: {:?} than expected List", v),
+        }
+    }
+
     #[test]
     fn display_decoder() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
@@ -245,4 +844,36 @@ mod tests {
             format!("{:?}", decoder).starts_with("ProtoDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client {")
         )
     }
+
+    #[tokio::test]
+    async fn test_decoder_complex_preloaded_reference() {
+        let _m = mock("GET", "/schemas/ids/6?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body_with_reference(
+                get_proto_complex(),
+                2,
+                get_proto_complex_references(),
+            ))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let mut preloaded = HashMap::new();
+        preloaded.insert(
+            String::from("result.proto"),
+            get_proto_result().replace("\\\"", "\""),
+        );
+        decoder.set_preloaded_references(preloaded);
+        let proto_test = decoder
+            .decode(Some(get_proto_complex_proto_test_message()))
+            .await
+            .unwrap();
+
+        let message = match proto_test {
+            Value::Message(x) => *x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+        assert_eq!(message.fields[1].value, Value::Int64(1))
+    }
 }
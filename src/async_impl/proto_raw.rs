@@ -6,13 +6,15 @@ use crate::async_impl::schema_registry::{
 };
 use crate::error::SRCError;
 use crate::proto_raw_common::{
-    to_bytes, to_bytes_single_message, to_decode_context, DecodeContext, EncodeContext,
+    find_field_raw, to_bytes, to_bytes_single_message, to_decode_context, DecodeContext,
+    EncodeContext,
 };
 use crate::proto_resolver::{resolve_name, to_index_and_data, IndexResolver};
 use crate::schema_registry_common::{
-    get_bytes_result, get_subject, BytesResult, RegisteredSchema, SchemaType, SubjectNameStrategy,
+    get_bytes_result, get_subject, BytesResult, CacheCounters, CacheStats, PayloadCompression,
+    RegisteredSchema, SchemaType, SubjectNameStrategy,
 };
-use futures::future::{BoxFuture, Shared};
+use futures::future::{join_all, BoxFuture, Shared};
 use futures::FutureExt;
 
 /// Encoder that works by prepending the correct bytes in order to make it valid schema registry
@@ -40,6 +42,15 @@ impl<'a> ProtoRawEncoder<'a> {
             None => true,
         });
     }
+    /// Waits until all the schema's in the cache are fetched or registered, and returns the first
+    /// error, if any. Can be used on shutdown so no registration is left unfinished.
+    pub async fn flush(&self) -> Result<(), SRCError> {
+        let pending: Vec<_> = self.cache.values().cloned().collect();
+        for result in join_all(pending).await {
+            result?;
+        }
+        Ok(())
+    }
     /// Encodes the bytes by adding a few bytes to the message with additional information. The full
     /// names is the optional package followed with the message name, and optionally inner messages.
     pub async fn encode(
@@ -101,6 +112,8 @@ impl<'a> ProtoRawEncoder<'a> {
 pub struct ProtoRawDecoder<'a> {
     sr_settings: SrSettings,
     cache: HashMap<u32, Shared<BoxFuture<'a, Result<DecodeContext, SRCError>>>, RandomState>,
+    counters: CacheCounters,
+    compression: PayloadCompression,
 }
 
 impl<'a> ProtoRawDecoder<'a> {
@@ -113,16 +126,35 @@ impl<'a> ProtoRawDecoder<'a> {
         ProtoRawDecoder {
             sr_settings,
             cache: HashMap::new(),
+            counters: CacheCounters::default(),
+            compression: PayloadCompression::None,
         }
     }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<ProtoRawDecoder<'a>, SRCError> {
+        SrSettings::global().map(ProtoRawDecoder::new)
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
     pub fn remove_errors_from_cache(&mut self) {
+        let before = self.cache.len();
         self.cache.retain(|_, v| match v.peek() {
             Some(r) => r.is_ok(),
             None => true,
         });
+        self.counters.evicted(before - self.cache.len());
+    }
+    /// Gives a snapshot of the statistics of the cache, like the number of entries, and how often a
+    /// schema could be taken from the cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.counters.to_stats(self.cache.len())
+    }
+    /// Sets the compression of the bytes after the header, which will be decompressed before
+    /// decoding. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
     }
     /// Reads the bytes to get the name, and gives back the data bytes.
     pub async fn decode(
@@ -131,13 +163,29 @@ impl<'a> ProtoRawDecoder<'a> {
     ) -> Result<Option<RawDecodeResult>, SRCError> {
         match get_bytes_result(bytes) {
             BytesResult::Null => Ok(None),
-            BytesResult::Valid(id, bytes) => Ok(Some(self.deserialize(id, &bytes).await?)),
+            BytesResult::Valid(id, bytes) => Ok(Some(
+                self.deserialize(id, &self.compression.decompress(bytes)?)
+                    .await?,
+            )),
             BytesResult::Invalid(i) => Err(SRCError::non_retryable_without_cause(&*format!(
                 "Invalid bytes {:?}",
                 i
             ))),
         }
     }
+    /// Reads the raw bytes of a single field, without decoding the rest of the message. The wire
+    /// format is walked until the first occurrence of the field number. For length delimited
+    /// fields, like strings, bytes and embedded messages, the length prefix is left out.
+    pub async fn decode_field_raw(
+        &mut self,
+        bytes: Option<&[u8]>,
+        field_number: u32,
+    ) -> Result<Option<Vec<u8>>, SRCError> {
+        match self.decode(bytes).await? {
+            None => Ok(None),
+            Some(r) => find_field_raw(&r.bytes, field_number),
+        }
+    }
     /// The actual deserialization trying to get the id from the bytes to retrieve the schema, and
     /// using a reader transforms the bytes to a value.
     async fn deserialize(&mut self, id: u32, bytes: &[u8]) -> Result<RawDecodeResult, SRCError> {
@@ -148,15 +196,19 @@ impl<'a> ProtoRawDecoder<'a> {
         Ok(RawDecodeResult {
             schema,
             full_name,
-            bytes: data,
+            bytes: data.to_vec(),
         })
     }
     /// Gets the Context object, either from the cache, or from the schema registry and then putting
     /// it into the cache.
     fn get_context(&mut self, id: u32) -> &Shared<BoxFuture<'a, Result<DecodeContext, SRCError>>> {
         match self.cache.entry(id) {
-            Entry::Occupied(e) => &*e.into_mut(),
+            Entry::Occupied(e) => {
+                self.counters.hit();
+                &*e.into_mut()
+            }
             Entry::Vacant(e) => {
+                self.counters.miss();
                 let sr_settings = self.sr_settings.clone();
                 let v = async move {
                     match get_schema_by_id_and_type(id, &sr_settings, SchemaType::Protobuf).await {
@@ -331,12 +383,8 @@ mod tests {
 
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let mut encoder = ProtoRawEncoder::new(sr_settings);
-        let result_reference = SuppliedReference {
-            name: String::from("result.proto"),
-            subject: String::from("result.proto"),
-            schema: String::from(get_proto_result()),
-            references: vec![],
-        };
+        let result_reference =
+            SuppliedReference::new("result.proto", "result.proto", get_proto_result(), vec![]);
         let supplied_schema = SuppliedSchema {
             name: Some(String::from("test.proto")),
             schema_type: SchemaType::Protobuf,
@@ -447,6 +495,24 @@ mod tests {
         assert_eq!(raw_result.bytes, get_proto_complex_only_data())
     }
 
+    #[tokio::test]
+    async fn test_decode_field_raw() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 7))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoRawDecoder::new(sr_settings);
+        let beat = decoder
+            .decode_field_raw(Some(get_proto_hb_101()), 1)
+            .await
+            .unwrap();
+
+        assert_eq!(beat, Some(vec![101]))
+    }
+
     #[test]
     fn display_decoder() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
@@ -1,13 +1,20 @@
 use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::Cursor;
 
 use avro_rs::schema::{Name, Schema};
 use avro_rs::types::{Record, Value};
-use avro_rs::{to_avro_datum, to_value};
+use avro_rs::{from_avro_datum, from_value, to_avro_datum, to_value, Decimal};
+use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use serde_json::{value, Map};
 
 use crate::error::SRCError;
-use crate::schema_registry_common::{get_payload, SchemaType, SuppliedSchema};
+use crate::schema_registry_common::{
+    get_bytes_result, get_payload, BytesResult, ContentHasher, FieldDescriptor, PayloadCompression,
+    RegisteredSchema, SchemaInfo, SchemaType, SuppliedSchema,
+};
 
 /// Because we need both the resulting schema, as have a way of posting the schema as json, we use
 /// this struct so we keep them both together.
@@ -16,6 +23,7 @@ pub(crate) struct AvroSchema {
     pub(crate) id: u32,
     pub(crate) raw: String,
     pub(crate) parsed: Schema,
+    pub(crate) etag: Option<String>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -24,6 +32,49 @@ pub struct DecodeResult {
     pub value: Value,
 }
 
+impl DecodeResult {
+    /// Replaces all the union values with the value they contain. So a `["null", "string"]` field
+    /// will either be `Value::Null` or `Value::String`, instead of being wrapped in a
+    /// `Value::Union`.
+    pub fn flatten_unions(self) -> DecodeResult {
+        DecodeResult {
+            name: self.name,
+            value: flatten_unions(self.value),
+        }
+    }
+    /// Converts the value to json, a union with a null value will become a json null, other unions
+    /// will become the json of the contained value.
+    pub fn to_json(&self) -> Result<value::Value, SRCError> {
+        match value::Value::try_from(self.value.clone()) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(SRCError::non_retryable_with_cause(
+                e,
+                "Could not convert Avro value to json",
+            )),
+        }
+    }
+}
+
+fn flatten_unions(value: Value) -> Value {
+    match value {
+        Value::Union(v) => flatten_unions(*v),
+        Value::Record(fields) => Value::Record(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k, flatten_unions(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(flatten_unions).collect()),
+        Value::Map(items) => Value::Map(
+            items
+                .into_iter()
+                .map(|(k, v)| (k, flatten_unions(v)))
+                .collect(),
+        ),
+        v => v,
+    }
+}
+
 fn might_replace(
     val: value::Value,
     child: &value::Value,
@@ -95,7 +146,66 @@ pub(crate) fn replace_reference(parent: value::Value, child: value::Value) -> va
     }
 }
 
+/// Checks that all the values for fields with a fixed schema have exactly the size declared in the
+/// schema. Avro itself would only give a generic validation error, while we want to know which field
+/// is wrong.
+fn validate_fixed_sizes(schema: &Schema, value: &Value, path: &str) -> Result<(), SRCError> {
+    match (schema, value) {
+        (Schema::Fixed { size, .. }, Value::Fixed(_, bytes))
+        | (Schema::Fixed { size, .. }, Value::Bytes(bytes)) => {
+            if bytes.len() == *size {
+                Ok(())
+            } else {
+                Err(SRCError::non_retryable_without_cause(&format!(
+                    "field {} is fixed with size {}, but the value has {} bytes",
+                    path,
+                    size,
+                    bytes.len()
+                )))
+            }
+        }
+        (Schema::Record { fields, .. }, Value::Record(values)) => {
+            for (name, v) in values {
+                if let Some(field) = fields.iter().find(|f| &f.name == name) {
+                    let child_path = if path.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{}.{}", path, name)
+                    };
+                    validate_fixed_sizes(&field.schema, v, &child_path)?
+                }
+            }
+            Ok(())
+        }
+        (Schema::Array(inner), Value::Array(values)) => {
+            for v in values {
+                validate_fixed_sizes(inner, v, path)?
+            }
+            Ok(())
+        }
+        (Schema::Map(inner), Value::Map(values)) => {
+            for v in values.values() {
+                validate_fixed_sizes(inner, v, path)?
+            }
+            Ok(())
+        }
+        (Schema::Union(union), Value::Union(v)) => {
+            let non_null: Vec<&Schema> = union
+                .variants()
+                .iter()
+                .filter(|s| !matches!(s, Schema::Null))
+                .collect();
+            match non_null.as_slice() {
+                [only] => validate_fixed_sizes(only, v, path),
+                _ => Ok(()),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
 fn to_bytes(avro_schema: &AvroSchema, record: Value) -> Result<Vec<u8>, SRCError> {
+    validate_fixed_sizes(&avro_schema.parsed, &record, "")?;
     match to_avro_datum(&avro_schema.parsed, record) {
         Ok(v) => Ok(get_payload(avro_schema.id, v)),
         Err(e) => Err(SRCError::non_retryable_with_cause(
@@ -135,6 +245,7 @@ pub(crate) fn item_to_bytes(
 ) -> Result<Vec<u8>, SRCError> {
     match to_value(item)
         .map_err(|e| SRCError::non_retryable_with_cause(e, "Could not transform to avro_rs value"))
+        .and_then(|r| validate_fixed_sizes(&avro_schema.parsed, &r, "").map(|_| r))
         .map(|r| r.resolve(&avro_schema.parsed))
     {
         Ok(Ok(v)) => to_bytes(avro_schema, v),
@@ -143,6 +254,81 @@ pub(crate) fn item_to_bytes(
     }
 }
 
+/// Gets the schema id and the rest of the bytes for the message starting at the position, for
+/// bytes which contain multiple messages.
+pub(crate) fn frame_at(bytes: &[u8], position: usize) -> Result<(u32, Vec<u8>), SRCError> {
+    match get_bytes_result(Some(&bytes[position..])) {
+        BytesResult::Valid(id, payload) => Ok((id, payload)),
+        _ => Err(SRCError::non_retryable_without_cause(&format!(
+            "Bytes from position {} don't form a complete message",
+            position
+        ))),
+    }
+}
+
+/// Checks the payload is not compressed, since then it's not known where a value ends.
+pub(crate) fn check_uncompressed(compression: PayloadCompression) -> Result<(), SRCError> {
+    if compression == PayloadCompression::None {
+        Ok(())
+    } else {
+        Err(SRCError::non_retryable_without_cause(
+            "Multiple messages can't be decoded from compressed payloads",
+        ))
+    }
+}
+
+/// Reads a single value, leaving the reader at the end of the value, so when the bytes contain more
+/// it's known where the next part starts.
+pub(crate) fn read_value(
+    avro_schema: &AvroSchema,
+    reader: &mut Cursor<&[u8]>,
+) -> Result<DecodeResult, SRCError> {
+    match from_avro_datum(&avro_schema.parsed, reader, None) {
+        Ok(v) => Ok(DecodeResult {
+            name: get_name(&avro_schema.parsed),
+            value: v,
+        }),
+        Err(e) => Err(SRCError::non_retryable_with_cause(
+            e,
+            "Could not transform bytes using schema",
+        )),
+    }
+}
+
+/// Reads a single value written with the writer schema, and resolves it to the reader schema, so
+/// the value is as it would be when read with the reader schema.
+pub(crate) fn read_value_with_reader(
+    writer_schema: &AvroSchema,
+    reader_schema: &AvroSchema,
+    bytes: &[u8],
+) -> Result<DecodeResult, SRCError> {
+    match from_avro_datum(
+        &writer_schema.parsed,
+        &mut Cursor::new(bytes),
+        Some(&reader_schema.parsed),
+    ) {
+        Ok(v) => Ok(DecodeResult {
+            name: get_name(&reader_schema.parsed),
+            value: v,
+        }),
+        Err(e) => Err(SRCError::non_retryable_with_cause(
+            e,
+            "Could not resolve bytes to the reader schema",
+        )),
+    }
+}
+
+/// Maps the decoded value to the type, unions are mapped to the value they contain.
+pub(crate) fn value_into<T: DeserializeOwned>(value: &Value) -> Result<T, SRCError> {
+    match from_value::<T>(value) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(SRCError::non_retryable_with_cause(
+            e,
+            "Could not deserialize the decoded value",
+        )),
+    }
+}
+
 pub(crate) fn get_name(schema: &Schema) -> Option<Name> {
     match schema {
         Schema::Record { name: n, .. } => Some(n.clone()),
@@ -150,29 +336,463 @@ pub(crate) fn get_name(schema: &Schema) -> Option<Name> {
     }
 }
 
-pub fn get_supplied_schema(schema: &Schema) -> Box<SuppliedSchema> {
-    let name = match get_name(schema) {
+pub(crate) fn get_full_name(schema: &Schema) -> Option<String> {
+    match get_name(schema) {
         None => None,
         Some(n) => match n.namespace {
             None => Some(n.name),
             Some(ns) => Some(format!("{}.{}", ns, n.name)),
         },
+    }
+}
+
+/// Parses the Avro schema and returns the full name of it, `None` when the schema has no name,
+/// like a union or primitive.
+pub(crate) fn extract_avro_name(schema: &str) -> Result<Option<String>, SRCError> {
+    match Schema::parse_str(schema) {
+        Ok(s) => Ok(get_full_name(&s)),
+        Err(e) => Err(SRCError::non_retryable_with_cause(
+            e,
+            "Could not parse the supplied schema",
+        )),
+    }
+}
+
+/// Parses the Avro schema and gives the top-level information of it. Only records have fields, and
+/// only records and enums have documentation.
+pub(crate) fn describe_avro_schema(schema: &str) -> Result<SchemaInfo, SRCError> {
+    let schema = match Schema::parse_str(schema) {
+        Ok(s) => s,
+        Err(e) => {
+            return Err(SRCError::non_retryable_with_cause(
+                e,
+                "Could not parse the Avro schema",
+            ))
+        }
+    };
+    let (name, doc) = match &schema {
+        Schema::Record { name, doc, .. } | Schema::Enum { name, doc, .. } => {
+            (Some(name.clone()), doc.clone())
+        }
+        Schema::Fixed { name, .. } => (Some(name.clone()), None),
+        _ => (None, None),
     };
+    Ok(SchemaInfo {
+        namespace: name.as_ref().and_then(|n| n.namespace.clone()),
+        name: name.map(|n| n.name),
+        doc,
+        fields: describe_fields(&schema),
+    })
+}
+
+pub fn get_supplied_schema(schema: &Schema) -> Box<SuppliedSchema> {
     Box::from(SuppliedSchema {
-        name,
+        name: get_full_name(schema),
         schema_type: SchemaType::Avro,
         schema: schema.canonical_form(),
         references: vec![],
     })
 }
 
+/// Checks whether the decoded value conforms to the registered schema, for example that all the
+/// fields of records are present and enum values are valid symbols. The first violation found is
+/// returned as error, with the path of the field. The registered schema should be an Avro schema
+/// without references.
+pub fn validate_against_schema(
+    value: &Value,
+    registered_schema: &RegisteredSchema,
+) -> Result<(), SRCError> {
+    if registered_schema.schema_type != SchemaType::Avro {
+        return Err(SRCError::non_retryable_without_cause(&format!(
+            "type {:?}, is not supported",
+            registered_schema.schema_type
+        )));
+    }
+    let schema = match Schema::parse_str(&registered_schema.schema) {
+        Ok(s) => s,
+        Err(e) => {
+            return Err(SRCError::non_retryable_with_cause(
+                e,
+                "Could not parse the registered schema",
+            ))
+        }
+    };
+    match first_violation(&schema, value, "") {
+        None => Ok(()),
+        Some(v) => Err(SRCError::non_retryable_without_cause(&v)),
+    }
+}
+
+/// Value of the Avro decimal logical type, as the unscaled value and the scale, so the actual value
+/// is `unscaled * 10^-scale`. Avro stores the unscaled value as big-endian two's-complement bytes,
+/// which limits the values supported here to those fitting 128 bits.
+///
+/// This is a helper to be used manually, the decoders keep returning decimals as
+/// `Value::Decimal`, and the encoders expect them like that. Use `from_value` with the schema of
+/// the field on a decoded value, and `to_value` to create the value to encode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AvroDecimal {
+    pub unscaled: i128,
+    pub scale: usize,
+}
+
+impl AvroDecimal {
+    pub fn new(unscaled: i128, scale: usize) -> AvroDecimal {
+        AvroDecimal { unscaled, scale }
+    }
+
+    /// Reads the decimal from a decoded value, the scale is taken from the decimal schema of the
+    /// value. Gives an error when the value has more digits than the precision of the schema, or
+    /// when it doesn't fit 128 bits. A fixed bigger than 16 bytes is fine as long as the extra
+    /// bytes only extend the sign.
+    pub fn from_value(value: &Value, schema: &Schema) -> Result<AvroDecimal, SRCError> {
+        let (precision, scale) = decimal_metadata(schema)?;
+        let bytes = match value {
+            Value::Decimal(d) => match Vec::<u8>::try_from(d) {
+                Ok(b) => b,
+                Err(e) => {
+                    return Err(SRCError::non_retryable_with_cause(
+                        e,
+                        "Could not get the bytes of the decimal",
+                    ))
+                }
+            },
+            Value::Bytes(b) | Value::Fixed(_, b) => b.clone(),
+            v => {
+                return Err(SRCError::non_retryable_without_cause(&format!(
+                    "value {:?} is not a decimal",
+                    v
+                )))
+            }
+        };
+        let sign = if matches!(bytes.first(), Some(b) if b & 0x80 != 0) {
+            0xFF
+        } else {
+            0x00
+        };
+        let sign_extension = bytes
+            .iter()
+            .zip(bytes.iter().skip(1))
+            .take_while(|(b, next)| **b == sign && (**next & 0x80) == (sign & 0x80))
+            .count();
+        let significant = &bytes[sign_extension..];
+        if significant.len() > 16 {
+            return Err(SRCError::non_retryable_without_cause(&format!(
+                "decimal of {} bytes doesn't fit 128 bits",
+                significant.len()
+            )));
+        }
+        let mut extended = [sign; 16];
+        extended[16 - significant.len()..].copy_from_slice(significant);
+        let decimal = AvroDecimal::new(i128::from_be_bytes(extended), scale);
+        decimal.check_precision(precision)?;
+        Ok(decimal)
+    }
+
+    /// Creates the value to encode with the decimal schema. The scale should be the same as the
+    /// scale of the schema, and the number of digits may not exceed the precision. For a fixed
+    /// schema the bytes are sign extended to the size of the fixed.
+    pub fn to_value(&self, schema: &Schema) -> Result<Value, SRCError> {
+        let (precision, scale) = decimal_metadata(schema)?;
+        if self.scale != scale {
+            return Err(SRCError::non_retryable_without_cause(&format!(
+                "scale {} doesn't match the scale {} of the schema",
+                self.scale, scale
+            )));
+        }
+        self.check_precision(precision)?;
+        let bytes = self.unscaled.to_be_bytes();
+        let sign = if self.unscaled < 0 { 0xFF } else { 0x00 };
+        let needed = bytes
+            .iter()
+            .zip(bytes.iter().skip(1))
+            .take_while(|(b, next)| **b == sign && (**next & 0x80) == (sign & 0x80))
+            .count();
+        let minimal = &bytes[needed..];
+        let len = match schema {
+            Schema::Decimal { inner, .. } => match &**inner {
+                Schema::Fixed { size, .. } if *size < minimal.len() => {
+                    return Err(SRCError::non_retryable_without_cause(&format!(
+                        "decimal needs {} bytes, but the fixed has a size of {}",
+                        minimal.len(),
+                        size
+                    )))
+                }
+                Schema::Fixed { size, .. } => *size,
+                _ => minimal.len(),
+            },
+            _ => minimal.len(),
+        };
+        let mut result = vec![sign; len];
+        result[len - minimal.len()..].copy_from_slice(minimal);
+        Ok(Value::Decimal(Decimal::from(result)))
+    }
+
+    fn check_precision(&self, precision: usize) -> Result<(), SRCError> {
+        let digits = self.unscaled.unsigned_abs().to_string().len();
+        if digits > precision {
+            return Err(SRCError::non_retryable_without_cause(&format!(
+                "decimal has {} digits, which is more than the precision {} of the schema",
+                digits, precision
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AvroDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = format!(
+            "{:0>width$}",
+            self.unscaled.unsigned_abs(),
+            width = self.scale + 1
+        );
+        let (int, frac) = digits.split_at(digits.len() - self.scale);
+        let sign = if self.unscaled < 0 { "-" } else { "" };
+        if frac.is_empty() {
+            write!(f, "{}{}", sign, int)
+        } else {
+            write!(f, "{}{}.{}", sign, int, frac)
+        }
+    }
+}
+
+fn decimal_metadata(schema: &Schema) -> Result<(usize, usize), SRCError> {
+    match schema {
+        Schema::Decimal {
+            precision, scale, ..
+        } => Ok((*precision, *scale)),
+        s => Err(SRCError::non_retryable_without_cause(&format!(
+            "schema {:?} is not a decimal schema",
+            s
+        ))),
+    }
+}
+
+/// Gives the descriptors of the fields, when the schema is a record.
+pub(crate) fn describe_fields(schema: &Schema) -> Vec<FieldDescriptor> {
+    match schema {
+        Schema::Record { fields, .. } => fields
+            .iter()
+            .map(|f| describe_field(&f.name, &f.schema))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn describe_field(name: &str, schema: &Schema) -> FieldDescriptor {
+    let (schema, nullable) = match schema {
+        Schema::Union(union) if union.is_nullable() && union.variants().len() == 2 => {
+            match union.variants().iter().find(|s| !matches!(s, Schema::Null)) {
+                Some(s) => (s, true),
+                None => (schema, false),
+            }
+        }
+        _ => (schema, false),
+    };
+    let (schema, repeated) = match schema {
+        Schema::Array(inner) => (&**inner, true),
+        _ => (schema, false),
+    };
+    FieldDescriptor {
+        name: String::from(name),
+        type_name: type_name(schema),
+        nullable,
+        repeated,
+    }
+}
+
+fn type_name(schema: &Schema) -> String {
+    let name = match schema {
+        Schema::Null => "null",
+        Schema::Boolean => "boolean",
+        Schema::Int => "int",
+        Schema::Long => "long",
+        Schema::Float => "float",
+        Schema::Double => "double",
+        Schema::Bytes => "bytes",
+        Schema::String => "string",
+        Schema::Array(_) => "array",
+        Schema::Map(_) => "map",
+        Schema::Union(_) => "union",
+        Schema::Record { name, .. } | Schema::Enum { name, .. } | Schema::Fixed { name, .. } => {
+            return name.fullname(None)
+        }
+        Schema::Decimal { .. } => "decimal",
+        Schema::Uuid => "uuid",
+        Schema::Date => "date",
+        Schema::TimeMillis => "time-millis",
+        Schema::TimeMicros => "time-micros",
+        Schema::TimestampMillis => "timestamp-millis",
+        Schema::TimestampMicros => "timestamp-micros",
+        Schema::Duration => "duration",
+    };
+    String::from(name)
+}
+
+fn first_violation(schema: &Schema, value: &Value, path: &str) -> Option<String> {
+    let location = if path.is_empty() {
+        String::from("the value")
+    } else {
+        format!("field {}", path)
+    };
+    match (schema, value) {
+        (Schema::Record { fields, .. }, Value::Record(values)) => fields.iter().find_map(|f| {
+            let child_path = if path.is_empty() {
+                f.name.clone()
+            } else {
+                format!("{}.{}", path, f.name)
+            };
+            match values.iter().find(|(name, _)| name == &f.name) {
+                Some((_, v)) => first_violation(&f.schema, v, &child_path),
+                None => Some(format!("field {} is missing", child_path)),
+            }
+        }),
+        (Schema::Union(union), Value::Union(v)) => {
+            match union.variants().iter().find(|s| v.validate(s)) {
+                Some(s) => first_violation(s, v, path),
+                None => Some(format!(
+                    "{} does not match any of the union types",
+                    location
+                )),
+            }
+        }
+        (Schema::Array(inner), Value::Array(items)) => {
+            items.iter().find_map(|v| first_violation(inner, v, path))
+        }
+        (Schema::Map(inner), Value::Map(items)) => {
+            items.values().find_map(|v| first_violation(inner, v, path))
+        }
+        (Schema::Enum { symbols, .. }, Value::Enum(_, symbol)) if !symbols.contains(symbol) => {
+            Some(format!("{} has invalid symbol {}", location, symbol))
+        }
+        _ if !value.validate(schema) => Some(format!("{} does not match the schema", location)),
+        _ => None,
+    }
+}
+
+/// Gives a hash of the content of the value. Union wrappers are ignored, and the entries of maps are
+/// hashed ordered by key, so the hash doesn't depend on the order in which they were decoded.
+pub(crate) fn content_hash(value: &Value) -> u64 {
+    let mut hasher = ContentHasher::default();
+    hash_value(&mut hasher, value);
+    hasher.finish()
+}
+
+fn hash_value(hasher: &mut ContentHasher, value: &Value) {
+    match value {
+        Value::Null => hasher.tag(0),
+        Value::Boolean(b) => {
+            hasher.tag(1);
+            hasher.write(&[u8::from(*b)])
+        }
+        Value::Int(i) => {
+            hasher.tag(2);
+            hasher.write(&i.to_le_bytes())
+        }
+        Value::Long(l) => {
+            hasher.tag(3);
+            hasher.write(&l.to_le_bytes())
+        }
+        Value::Float(f) => {
+            hasher.tag(4);
+            hasher.write(&f.to_bits().to_le_bytes())
+        }
+        Value::Double(d) => {
+            hasher.tag(5);
+            hasher.write(&d.to_bits().to_le_bytes())
+        }
+        Value::Bytes(b) => {
+            hasher.tag(6);
+            hasher.write_sized(b)
+        }
+        Value::String(s) => {
+            hasher.tag(7);
+            hasher.write_sized(s.as_bytes())
+        }
+        Value::Fixed(_, b) => {
+            hasher.tag(8);
+            hasher.write_sized(b)
+        }
+        Value::Enum(_, symbol) => {
+            hasher.tag(9);
+            hasher.write_sized(symbol.as_bytes())
+        }
+        Value::Union(v) => hash_value(hasher, v),
+        Value::Array(items) => {
+            hasher.tag(10);
+            hasher.write(&(items.len() as u64).to_le_bytes());
+            for item in items {
+                hash_value(hasher, item)
+            }
+        }
+        Value::Map(entries) => {
+            hasher.tag(11);
+            hasher.write(&(entries.len() as u64).to_le_bytes());
+            let mut keys: Vec<&String> = entries.keys().collect();
+            keys.sort();
+            for key in keys {
+                hasher.write_sized(key.as_bytes());
+                hash_value(hasher, &entries[key])
+            }
+        }
+        Value::Record(fields) => {
+            hasher.tag(12);
+            hasher.write(&(fields.len() as u64).to_le_bytes());
+            for (name, v) in fields {
+                hasher.write_sized(name.as_bytes());
+                hash_value(hasher, v)
+            }
+        }
+        Value::Date(d) => {
+            hasher.tag(13);
+            hasher.write(&d.to_le_bytes())
+        }
+        Value::Decimal(d) => {
+            hasher.tag(14);
+            hasher.write_sized(&Vec::<u8>::try_from(d).unwrap_or_default())
+        }
+        Value::TimeMillis(t) => {
+            hasher.tag(15);
+            hasher.write(&t.to_le_bytes())
+        }
+        Value::TimeMicros(t) => {
+            hasher.tag(16);
+            hasher.write(&t.to_le_bytes())
+        }
+        Value::TimestampMillis(t) => {
+            hasher.tag(17);
+            hasher.write(&t.to_le_bytes())
+        }
+        Value::TimestampMicros(t) => {
+            hasher.tag(18);
+            hasher.write(&t.to_le_bytes())
+        }
+        Value::Duration(d) => {
+            hasher.tag(19);
+            hasher.write(&<[u8; 12]>::from(*d))
+        }
+        Value::Uuid(u) => {
+            hasher.tag(20);
+            hasher.write(u.as_bytes())
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use avro_rs::types::Value;
     use avro_rs::Schema;
+    use serde_json::json;
 
-    use crate::avro_common::{values_to_bytes, AvroSchema};
+    use crate::avro_common::{
+        content_hash, validate_against_schema, values_to_bytes, AvroDecimal, AvroSchema,
+        DecodeResult,
+    };
     use crate::error::SRCError;
+    use crate::schema_registry_common::{RegisteredSchema, SchemaType};
     use test_utils::{Atype, ConfirmAccountCreation, Heartbeat};
 
     #[test]
@@ -181,6 +801,7 @@ mod tests {
             id: 5,
             raw: "".to_string(),
             parsed: Schema::Boolean,
+            etag: None,
         };
         let result = values_to_bytes(&schema, vec![("beat", Value::Long(3))]);
         assert_eq!(
@@ -199,6 +820,7 @@ mod tests {
             id: 5,
             raw: String::from(r#"{"type":"record","name":"Name","namespace":"nl.openweb.data","fields":[{"name":"name","type":"string","avro.java.string":"String"}]}"#),
             parsed: Schema::parse_str(r#"{"type":"record","name":"Name","namespace":"nl.openweb.data","fields":[{"name":"name","type":"string","avro.java.string":"String"}]}"#).unwrap(),
+            etag: None,
         };
         let err = values_to_bytes(&schema, vec![("beat", Value::Long(3))]).unwrap_err();
         assert_eq!(err.error, "Could not get Avro bytes")
@@ -213,6 +835,7 @@ mod tests {
             parsed: Schema::parse_str(
                 r#"{"type":"record","name":"Name","namespace":"nl.openweb.data","fields":[{"name":"name","type":"string","avro.java.string":"String"}]}"#,
             ).unwrap(),
+            etag: None,
         };
         let err = crate::avro_common::item_to_bytes(&schema, Heartbeat { beat: 3 }).unwrap_err();
         assert_eq!(err.error, "Failed to resolve")
@@ -228,6 +851,7 @@ mod tests {
             parsed: Schema::parse_str(
                 r#"{"type":"record","name":"ConfirmAccountCreation","namespace":"nl.openweb.data","fields":[{"name":"id","type":{"type":"fixed","name":"Uuid","size":16}},{"name":"a_type","type":{"type":"enum","name":"Atype","symbols":["AUTO","MANUAL"]}}]}"#,
             ).unwrap(),
+            etag: None,
         };
         let item = ConfirmAccountCreation {
             id: [
@@ -238,4 +862,251 @@ mod tests {
         let err = crate::avro_common::item_to_bytes(&schema, item).unwrap_err();
         assert_eq!(err.error, "Failed to resolve")
     }
+
+    fn fixed_schema() -> AvroSchema {
+        let raw = r#"{"type":"record","name":"Hashed","namespace":"nl.openweb.data","fields":[{"name":"hash","type":{"type":"fixed","name":"Hash","size":4}},{"name":"inner","type":["null",{"type":"record","name":"Inner","fields":[{"name":"id","type":{"type":"fixed","name":"Id","size":2}}]}]}]}"#;
+        AvroSchema {
+            id: 7,
+            raw: String::from(raw),
+            parsed: Schema::parse_str(raw).unwrap(),
+            etag: None,
+        }
+    }
+
+    #[test]
+    fn values_to_bytes_fixed_correct_size() {
+        let result = values_to_bytes(
+            &fixed_schema(),
+            vec![
+                ("hash", Value::Fixed(4, vec![1, 2, 3, 4])),
+                ("inner", Value::Union(Box::new(Value::Null))),
+            ],
+        )
+        .unwrap();
+        assert_eq!(result, vec![0, 0, 0, 0, 7, 1, 2, 3, 4, 0])
+    }
+
+    #[test]
+    fn values_to_bytes_fixed_from_bytes_too_short() {
+        let err = values_to_bytes(
+            &fixed_schema(),
+            vec![
+                ("hash", Value::Bytes(vec![1, 2, 3])),
+                ("inner", Value::Union(Box::new(Value::Null))),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.error,
+            "field hash is fixed with size 4, but the value has 3 bytes"
+        )
+    }
+
+    #[test]
+    fn values_to_bytes_nested_fixed_too_long() {
+        let inner = Value::Record(vec![(String::from("id"), Value::Fixed(3, vec![1, 2, 3]))]);
+        let err = values_to_bytes(
+            &fixed_schema(),
+            vec![
+                ("hash", Value::Fixed(4, vec![1, 2, 3, 4])),
+                ("inner", Value::Union(Box::new(inner))),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.error,
+            "field inner.id is fixed with size 2, but the value has 3 bytes"
+        )
+    }
+
+    fn nullable_result(value: Value) -> DecodeResult {
+        DecodeResult {
+            name: None,
+            value: Value::Record(vec![(String::from("name"), Value::Union(Box::new(value)))]),
+        }
+    }
+
+    #[test]
+    fn flatten_nullable_union() {
+        let null = nullable_result(Value::Null).flatten_unions();
+        let present = nullable_result(Value::String(String::from("foo"))).flatten_unions();
+        assert_eq!(
+            null.value,
+            Value::Record(vec![(String::from("name"), Value::Null)])
+        );
+        assert_eq!(
+            present.value,
+            Value::Record(vec![(
+                String::from("name"),
+                Value::String(String::from("foo"))
+            )])
+        )
+    }
+
+    #[test]
+    fn nullable_union_to_json() {
+        let null = nullable_result(Value::Null).to_json().unwrap();
+        let present = nullable_result(Value::String(String::from("foo")))
+            .to_json()
+            .unwrap();
+        assert_eq!(null, json!({ "name": null }));
+        assert_eq!(present, json!({ "name": "foo" }))
+    }
+
+    fn registered_schema() -> RegisteredSchema {
+        RegisteredSchema::new(
+            1,
+            SchemaType::Avro,
+            String::from(
+                r#"{"type":"record","name":"Account","namespace":"nl.openweb.data","fields":[{"name":"id","type":"long"},{"name":"details","type":{"type":"record","name":"Details","fields":[{"name":"a_type","type":{"type":"enum","name":"Atype","symbols":["AUTO","MANUAL"]}}]}}]}"#,
+            ),
+            vec![],
+        )
+    }
+
+    fn details(symbol: &str) -> Value {
+        Value::Record(vec![(
+            String::from("a_type"),
+            Value::Enum(0, String::from(symbol)),
+        )])
+    }
+
+    #[test]
+    fn validate_valid_value() {
+        let value = Value::Record(vec![
+            (String::from("id"), Value::Long(1)),
+            (String::from("details"), details("AUTO")),
+        ]);
+        assert_eq!(
+            validate_against_schema(&value, &registered_schema()),
+            Ok(())
+        )
+    }
+
+    #[test]
+    fn validate_missing_field() {
+        let value = Value::Record(vec![(String::from("details"), details("AUTO"))]);
+        let error = validate_against_schema(&value, &registered_schema()).unwrap_err();
+        assert_eq!(error.error, "field id is missing")
+    }
+
+    #[test]
+    fn validate_invalid_nested_enum() {
+        let value = Value::Record(vec![
+            (String::from("id"), Value::Long(1)),
+            (String::from("details"), details("BOGUS")),
+        ]);
+        let error = validate_against_schema(&value, &registered_schema()).unwrap_err();
+        assert_eq!(error.error, "field details.a_type has invalid symbol BOGUS")
+    }
+
+    #[test]
+    fn content_hash_ignores_map_order() {
+        let mut first = HashMap::new();
+        first.insert(String::from("a"), Value::Long(1));
+        first.insert(String::from("b"), Value::Long(2));
+        let mut second = HashMap::with_capacity(8);
+        second.insert(String::from("b"), Value::Long(2));
+        second.insert(String::from("a"), Value::Long(1));
+        let mut other = HashMap::new();
+        other.insert(String::from("a"), Value::Long(2));
+        other.insert(String::from("b"), Value::Long(1));
+
+        assert_eq!(
+            content_hash(&Value::Map(first.clone())),
+            content_hash(&Value::Map(second))
+        );
+        assert_ne!(
+            content_hash(&Value::Map(first)),
+            content_hash(&Value::Map(other))
+        )
+    }
+
+    #[test]
+    fn content_hash_is_stable() {
+        let value = Value::Record(vec![(String::from("beat"), Value::Long(3))]);
+        assert_eq!(content_hash(&value), 18091490173789179260)
+    }
+
+    #[test]
+    fn decimal_round_trip() {
+        let bytes_schema = Schema::parse_str(
+            r#"{"type":"bytes","logicalType":"decimal","precision":6,"scale":2}"#,
+        )
+        .unwrap();
+        let fixed_schema = Schema::parse_str(
+            r#"{"type":{"type":"fixed","name":"amount","size":4},"logicalType":"decimal","precision":6,"scale":2}"#,
+        )
+        .unwrap();
+        for schema in [bytes_schema, fixed_schema] {
+            for unscaled in [0, 128, -129, 999_999, -999_999] {
+                let decimal = AvroDecimal::new(unscaled, 2);
+                let bytes =
+                    avro_rs::to_avro_datum(&schema, decimal.to_value(&schema).unwrap()).unwrap();
+                let value = avro_rs::from_avro_datum(&schema, &mut &bytes[..], None).unwrap();
+                assert_eq!(AvroDecimal::from_value(&value, &schema), Ok(decimal));
+            }
+        }
+        assert_eq!(AvroDecimal::new(-1205, 2).to_string(), "-12.05");
+        assert_eq!(AvroDecimal::new(5, 2).to_string(), "0.05");
+    }
+
+    #[test]
+    fn decimal_not_matching_schema() {
+        let schema = Schema::parse_str(
+            r#"{"type":"bytes","logicalType":"decimal","precision":4,"scale":2}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            AvroDecimal::new(12345, 2)
+                .to_value(&schema)
+                .unwrap_err()
+                .error,
+            "decimal has 5 digits, which is more than the precision 4 of the schema"
+        );
+        assert_eq!(
+            AvroDecimal::new(1234, 3)
+                .to_value(&schema)
+                .unwrap_err()
+                .error,
+            "scale 3 doesn't match the scale 2 of the schema"
+        );
+        assert_eq!(
+            AvroDecimal::from_value(&Value::Bytes(vec![0x01, 0xE2, 0x40]), &schema)
+                .unwrap_err()
+                .error,
+            "decimal has 6 digits, which is more than the precision 4 of the schema"
+        )
+    }
+
+    #[test]
+    fn decimal_from_wide_fixed() {
+        let schema = Schema::parse_str(
+            r#"{"type":{"type":"fixed","name":"amount","size":20},"logicalType":"decimal","precision":38,"scale":2}"#,
+        )
+        .unwrap();
+        for unscaled in [0, 1205, -1205, 10_i128.pow(37), -(10_i128.pow(37))] {
+            let decimal = AvroDecimal::new(unscaled, 2);
+            let value = decimal.to_value(&schema).unwrap();
+            assert_eq!(AvroDecimal::from_value(&value, &schema), Ok(decimal));
+        }
+
+        let mut too_big = vec![0x00; 20];
+        too_big[3] = 0x01;
+        assert_eq!(
+            AvroDecimal::from_value(&Value::Fixed(20, too_big), &schema)
+                .unwrap_err()
+                .error,
+            "decimal of 17 bytes doesn't fit 128 bits"
+        );
+        let mut too_small = vec![0xFF; 20];
+        too_small[3] = 0x80;
+        assert_eq!(
+            AvroDecimal::from_value(&Value::Fixed(20, too_small), &schema)
+                .unwrap_err()
+                .error,
+            "decimal of 17 bytes doesn't fit 128 bits"
+        )
+    }
 }
@@ -27,20 +27,25 @@ use std::collections::HashMap;
 use std::io::Cursor;
 
 use avro_rs::types::Value;
-use avro_rs::{from_avro_datum, Schema};
+use avro_rs::Schema;
+use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 use serde_json::Value as JsonValue;
 
 use crate::avro_common::{
-    get_name, item_to_bytes, replace_reference, values_to_bytes, AvroSchema, DecodeResult,
+    check_uncompressed, content_hash, describe_fields, frame_at, item_to_bytes, read_value,
+    read_value_with_reader, replace_reference, value_into, values_to_bytes, AvroSchema,
+    DecodeResult,
 };
 use crate::blocking::schema_registry::{
-    get_referenced_schema, get_schema_by_id_and_type, get_schema_by_subject, SrSettings,
+    get_latest_schema_if_changed, get_referenced_schema, get_schema_by_id_and_type,
+    get_schema_by_subject, SrSettings,
 };
 use crate::error::SRCError;
 use crate::schema_registry_common::{
-    get_bytes_result, get_subject, BytesResult, RegisteredReference, RegisteredSchema, SchemaType,
-    SubjectNameStrategy,
+    extend_reference_path, get_bytes_result, get_id_and_data, get_schema, get_subject, BatchResult,
+    BytesResult, CacheCounters, CacheStats, DescribedValue, LatestSchema, PayloadCompression,
+    RegisteredReference, RegisteredSchema, SchemaType, SubjectNameStrategy,
 };
 
 /// A decoder used to transform bytes to a Value object
@@ -77,6 +82,8 @@ use crate::schema_registry_common::{
 pub struct AvroDecoder {
     sr_settings: SrSettings,
     cache: HashMap<u32, Result<AvroSchema, SRCError>, RandomState>,
+    counters: CacheCounters,
+    compression: PayloadCompression,
 }
 
 impl AvroDecoder {
@@ -89,8 +96,15 @@ impl AvroDecoder {
         AvroDecoder {
             sr_settings,
             cache: HashMap::new(),
+            counters: CacheCounters::default(),
+            compression: PayloadCompression::None,
         }
     }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<AvroDecoder, SRCError> {
+        SrSettings::global().map(AvroDecoder::new)
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
@@ -114,7 +128,7 @@ impl AvroDecoder {
     ///
     /// let heartbeat = decoder.decode(Some(&bytes));
     ///
-    /// assert_eq!(heartbeat, Err(SRCError::new("Could not get raw schema from response", None, false).into_cache()));
+    /// assert_eq!(heartbeat, Err(SRCError::schema_not_found(2).into_cache()));
     ///
     /// let _m = mock("GET", "/schemas/ids/2?deleted=true")
     ///     .with_status(200)
@@ -123,7 +137,7 @@ impl AvroDecoder {
     ///     .create();
     ///
     /// let heartbeat = decoder.decode(Some(&bytes));
-    /// assert_eq!(heartbeat, Err(SRCError::new("Could not get raw schema from response", None, false).into_cache()));
+    /// assert_eq!(heartbeat, Err(SRCError::schema_not_found(2).into_cache()));
     ///
     /// decoder.remove_errors_from_cache();
     ///
@@ -131,7 +145,19 @@ impl AvroDecoder {
     /// assert_eq!(heartbeat, Value::Record(vec![("beat".to_string(), Value::Long(3))]))
     /// ```
     pub fn remove_errors_from_cache(&mut self) {
+        let before = self.cache.len();
         self.cache.retain(|_, v| v.is_ok());
+        self.counters.evicted(before - self.cache.len());
+    }
+    /// Gives a snapshot of the statistics of the cache, like the number of entries, and how often a
+    /// schema could be taken from the cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.counters.to_stats(self.cache.len())
+    }
+    /// Sets the compression of the bytes after the header, which will be decompressed before
+    /// decoding. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
     }
     /// Decodes bytes into a value.
     /// The choice to use Option<&[u8]> as type us made so it plays nice with the BorrowedMessage
@@ -159,29 +185,151 @@ impl AvroDecoder {
                 name: None,
                 value: Value::Null,
             }),
-            BytesResult::Valid(id, bytes) => self.deserialize(id, &bytes),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?)
+            }
             BytesResult::Invalid(bytes) => Err(SRCError::non_retryable_without_cause(&*format!(
                 "Invalid bytes {:?}",
                 bytes
             ))),
         }
     }
+    /// Decodes bytes like decode, and maps the value to the type, which should match the schema.
+    /// When the bytes are null `None` is returned. When the id in the bytes is of another type of
+    /// schema, a non-retryable error is returned.
+    pub fn decode_into<T: DeserializeOwned>(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<Option<T>, SRCError> {
+        if let BytesResult::Null = get_bytes_result(bytes) {
+            return Ok(None);
+        }
+        let value = self.decode(bytes)?.value;
+        value_into(&value).map(Some)
+    }
+    /// Decodes bytes like decode, and also gives the schema id from the bytes, for example to log
+    /// which schema was used. Null bytes, or bytes without valid framing, give an error.
+    pub fn decode_with_id(&mut self, bytes: Option<&[u8]>) -> Result<(u32, Value), SRCError> {
+        let (id, data) = get_id_and_data(bytes)?;
+        let value = self
+            .deserialize(id, &self.compression.decompress(data)?)?
+            .value;
+        Ok((id, value))
+    }
+    /// Decodes bytes like decode, and also gives a hash of the decoded content. The hash is the same
+    /// across runs and platforms, so it can be used to detect duplicate messages.
+    pub fn decode_and_hash(&mut self, bytes: Option<&[u8]>) -> Result<(Value, u64), SRCError> {
+        let value = self.decode(bytes)?.value;
+        let hash = content_hash(&value);
+        Ok((value, hash))
+    }
+    /// Decodes the bytes with the latest schema of the subject as reader schema, the schema of the id
+    /// in the bytes is used as writer schema. This is meant to check whether data can be read with
+    /// the latest schema, for normal decoding use decode. The latest schema is not cached.
+    pub fn decode_with_subject_latest(
+        &mut self,
+        subject_name_strategy: &SubjectNameStrategy,
+        bytes: Option<&[u8]>,
+    ) -> Result<DecodeResult, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(DecodeResult {
+                name: None,
+                value: Value::Null,
+            }),
+            BytesResult::Valid(id, bytes) => {
+                let writer_schema = self.get_schema(id).clone()?;
+                let registered_schema =
+                    get_schema_by_subject(&self.sr_settings, subject_name_strategy)?;
+                let reader_schema = to_avro_schema(&self.sr_settings, registered_schema)?;
+                read_value_with_reader(
+                    &writer_schema,
+                    &reader_schema,
+                    &self.compression.decompress(bytes)?,
+                )
+            }
+            BytesResult::Invalid(bytes) => Err(SRCError::non_retryable_without_cause(&format!(
+                "Invalid bytes {:?}",
+                bytes
+            ))),
+        }
+    }
+    /// Decodes bytes like decode, but also gives the declared types of the fields of the record, as
+    /// derived from the schema.
+    pub fn decode_with_field_types(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<DescribedValue<DecodeResult>, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(DescribedValue {
+                value: DecodeResult {
+                    name: None,
+                    value: Value::Null,
+                },
+                fields: Vec::new(),
+            }),
+            BytesResult::Valid(id, bytes) => {
+                let bytes = self.compression.decompress(bytes)?;
+                match self.get_schema(id) {
+                    Ok(s) => Ok(DescribedValue {
+                        value: read_value(s, &mut Cursor::new(&bytes[..]))?,
+                        fields: describe_fields(&s.parsed),
+                    }),
+                    Err(e) => Err(Clone::clone(e)),
+                }
+            }
+            BytesResult::Invalid(bytes) => Err(SRCError::non_retryable_without_cause(&format!(
+                "Invalid bytes {:?}",
+                bytes
+            ))),
+        }
+    }
+    /// Decodes a batch of records, only the records with valid framing are decoded, null and invalid
+    /// records are returned as skipped, so they can be handled separately. The batch is aborted
+    /// when one of the valid records can't be decoded.
+    pub fn decode_batch(
+        &mut self,
+        items: &[Option<&[u8]>],
+    ) -> Result<BatchResult<DecodeResult>, SRCError> {
+        let mut result = BatchResult {
+            decoded: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for (index, bytes) in items.iter().enumerate() {
+            match get_bytes_result(*bytes) {
+                BytesResult::Valid(id, bytes) => {
+                    let value = self.deserialize(id, &self.compression.decompress(bytes)?)?;
+                    result.decoded.push((index, value))
+                }
+                skipped => result.skipped.push((index, skipped)),
+            }
+        }
+        Ok(result)
+    }
+    /// Decodes bytes containing multiple concatenated messages, each with its own magic byte and
+    /// schema id, into values in the same order. Avro doesn't need the length of a value, so the
+    /// next message is assumed to start right after the end of the previous value. Bytes which don't
+    /// form a complete message, including a trailing part, give an error. Can't be combined with a
+    /// compressed payload, since then the end of the value isn't known.
+    pub fn decode_all(&mut self, bytes: &[u8]) -> Result<Vec<DecodeResult>, SRCError> {
+        check_uncompressed(self.compression)?;
+        let mut results = Vec::new();
+        let mut position = 0;
+        while position < bytes.len() {
+            let (id, payload) = frame_at(bytes, position)?;
+            let mut reader = Cursor::new(&payload[..]);
+            match self.get_schema(id) {
+                Ok(s) => results.push(read_value(s, &mut reader)?),
+                Err(e) => return Err(Clone::clone(e)),
+            }
+            position += 5 + reader.position() as usize;
+        }
+        Ok(results)
+    }
     /// The actual deserialization trying to get the id from the bytes to retrieve the schema, and
     /// using a reader transforms the bytes to a value.
     fn deserialize(&mut self, id: u32, bytes: &[u8]) -> Result<DecodeResult, SRCError> {
-        let schema = self.get_schema(id);
-        let mut reader = Cursor::new(bytes);
-        match schema {
-            Ok(s) => match from_avro_datum(&s.parsed, &mut reader, None) {
-                Ok(v) => Ok(DecodeResult {
-                    name: get_name(&s.parsed),
-                    value: v,
-                }),
-                Err(e) => Err(SRCError::non_retryable_with_cause(
-                    e,
-                    "Could not transform bytes using schema",
-                )),
-            },
+        match self.get_schema(id) {
+            Ok(s) => read_value(s, &mut Cursor::new(bytes)),
             Err(e) => Err(Clone::clone(e)),
         }
     }
@@ -189,8 +337,12 @@ impl AvroDecoder {
     fn get_schema(&mut self, id: u32) -> &Result<AvroSchema, SRCError> {
         let sr_settings = &self.sr_settings;
         match self.cache.entry(id) {
-            Entry::Occupied(e) => &*e.into_mut(),
+            Entry::Occupied(e) => {
+                self.counters.hit();
+                &*e.into_mut()
+            }
             Entry::Vacant(e) => {
+                self.counters.miss();
                 let v = match get_schema_by_id_and_type(id, sr_settings, SchemaType::Avro) {
                     Ok(registered_schema) => to_avro_schema(sr_settings, registered_schema),
                     Err(e) => Err(e.into_cache()),
@@ -199,6 +351,15 @@ impl AvroDecoder {
             }
         }
     }
+    #[cfg(all(feature = "json", feature = "proto_decoder"))]
+    /// Puts a schema that was already fetched, for example to detect the schema type, in the cache,
+    /// so it's not fetched again when decoding.
+    pub(crate) fn add_to_cache(&mut self, id: u32, registered_schema: RegisteredSchema) {
+        if let Entry::Vacant(e) = self.cache.entry(id) {
+            self.counters.miss();
+            e.insert(to_avro_schema(&self.sr_settings, registered_schema));
+        }
+    }
 }
 
 /// An encoder used to transform a Value object to bytes
@@ -249,6 +410,7 @@ impl AvroDecoder {
 pub struct AvroEncoder {
     sr_settings: SrSettings,
     cache: HashMap<String, Result<AvroSchema, SRCError>, RandomState>,
+    compression: PayloadCompression,
 }
 
 impl AvroEncoder {
@@ -289,8 +451,14 @@ impl AvroEncoder {
         AvroEncoder {
             sr_settings,
             cache: HashMap::new(),
+            compression: PayloadCompression::None,
         }
     }
+    /// Sets the compression of the bytes after the header, the header itself is not compressed. The
+    /// decoder needs to use the same compression. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
@@ -314,7 +482,7 @@ impl AvroEncoder {
     ///     .create();
     ///
     /// let bytes = encoder.encode(vec![("beat", Value::Long(3))], &strategy);
-    /// assert_eq!(bytes, Err(SRCError::new("Could not get id from response", None, false).into_cache()));
+    /// assert_eq!(bytes, Err(SRCError::new("schema registry responded with error code 40403", Some(String::from("Schema not found")), false).with_status(404).with_registry_error(40403, "Schema not found").into_cache()));
     ///
     /// let _m = mock("GET", "/subjects/nl.openweb.data.Heartbeat/versions/latest")
     ///     .with_status(200)
@@ -323,7 +491,7 @@ impl AvroEncoder {
     ///     .create();
     ///
     /// let bytes = encoder.encode(vec![("beat", Value::Long(3))], &strategy);
-    /// assert_eq!(bytes, Err(SRCError::new("Could not get id from response", None, false).into_cache()));
+    /// assert_eq!(bytes, Err(SRCError::new("schema registry responded with error code 40403", Some(String::from("Schema not found")), false).with_status(404).with_registry_error(40403, "Schema not found").into_cache()));
     ///
     /// encoder.remove_errors_from_cache();
     ///
@@ -364,8 +532,9 @@ impl AvroEncoder {
         subject_name_strategy: &SubjectNameStrategy,
     ) -> Result<Vec<u8>, SRCError> {
         let key = get_subject(subject_name_strategy)?;
+        let compression = self.compression;
         match self.get_schema_and_id(key, subject_name_strategy) {
-            Ok(avro_schema) => values_to_bytes(&avro_schema, values),
+            Ok(avro_schema) => compression.compress_payload(values_to_bytes(&avro_schema, values)?),
             Err(e) => Err(Clone::clone(e)),
         }
     }
@@ -421,8 +590,9 @@ impl AvroEncoder {
         subject_name_strategy: &SubjectNameStrategy,
     ) -> Result<Vec<u8>, SRCError> {
         let key = get_subject(subject_name_strategy)?;
+        let compression = self.compression;
         match self.get_schema_and_id(key, subject_name_strategy) {
-            Ok(avro_schema) => item_to_bytes(&avro_schema, item),
+            Ok(avro_schema) => compression.compress_payload(item_to_bytes(&avro_schema, item)?),
             Err(e) => Err(Clone::clone(e)),
         }
     }
@@ -436,23 +606,72 @@ impl AvroEncoder {
         match self.cache.entry(key) {
             Entry::Occupied(e) => &*e.into_mut(),
             Entry::Vacant(e) => {
-                let v = match get_schema_by_subject(sr_settings, &subject_name_strategy) {
-                    Ok(registered_schema) => to_avro_schema(sr_settings, registered_schema),
+                let v = match get_schema_for_encoding(sr_settings, &subject_name_strategy) {
+                    Ok((registered_schema, etag)) => to_avro_schema(sr_settings, registered_schema)
+                        .map(|avro_schema| AvroSchema {
+                            etag,
+                            ..avro_schema
+                        }),
                     Err(e) => Err(e.into_cache()),
                 };
                 &*e.insert(v)
             }
         }
     }
+    /// Checks whether the cached latest schema's of subjects are still the latest, by sending the
+    /// ETag they were fetched with. Unchanged schema's are not downloaded again, changed ones are
+    /// replaced so they are used for the next records. This only has effect when the schema
+    /// registry sends ETags, schema's supplied with the subject name strategy are never refreshed.
+    pub fn refresh_latest(&mut self) -> Result<(), SRCError> {
+        let sr_settings = &self.sr_settings;
+        for (subject, cached) in self.cache.iter_mut() {
+            let etag = match cached {
+                Ok(AvroSchema {
+                    etag: Some(etag), ..
+                }) => etag.clone(),
+                _ => continue,
+            };
+            if let LatestSchema::Modified { schema, etag } =
+                get_latest_schema_if_changed(sr_settings, subject, Some(&etag))?
+            {
+                *cached = to_avro_schema(sr_settings, schema).map(|avro_schema| AvroSchema {
+                    etag,
+                    ..avro_schema
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Gets the schema to encode with, for the latest schema of the subject together with the ETag of
+/// the response, so it can be refreshed cheaply.
+fn get_schema_for_encoding(
+    sr_settings: &SrSettings,
+    subject_name_strategy: &SubjectNameStrategy,
+) -> Result<(RegisteredSchema, Option<String>), SRCError> {
+    if get_schema(subject_name_strategy).is_some() {
+        return get_schema_by_subject(sr_settings, subject_name_strategy).map(|s| (s, None));
+    }
+    let subject = get_subject(subject_name_strategy)?;
+    match get_latest_schema_if_changed(sr_settings, &subject, None)? {
+        LatestSchema::Modified { schema, etag } => Ok((schema, etag)),
+        LatestSchema::NotModified => Err(SRCError::non_retryable_without_cause(
+            "schema registry responded with not modified, while no etag was sent",
+        )),
+    }
 }
 
 fn add_references(
     sr_settings: &SrSettings,
     json_value: JsonValue,
     references: &[RegisteredReference],
+    path: &[String],
 ) -> Result<JsonValue, SRCError> {
     let mut new_value = json_value;
     for r in references.iter() {
+        let child_path =
+            extend_reference_path(path, &r.subject, sr_settings.max_reference_depth())?;
         let registered_schema = match get_referenced_schema(sr_settings, r) {
             Ok(v) => v,
             Err(e) => {
@@ -472,7 +691,12 @@ fn add_references(
             }
         };
         new_value = replace_reference(new_value, child);
-        new_value = match add_references(sr_settings, new_value, &registered_schema.references) {
+        new_value = match add_references(
+            sr_settings,
+            new_value,
+            &registered_schema.references,
+            &child_path,
+        ) {
             Ok(v) => v,
             Err(e) => return Err(e),
         }
@@ -494,7 +718,8 @@ fn to_avro_schema(
         }
     }
     let main_schema = match serde_json::from_str(&*registered_schema.schema) {
-        Ok(v) => match add_references(sr_settings, v, registered_schema.references.as_slice()) {
+        Ok(v) => match add_references(sr_settings, v, registered_schema.references.as_slice(), &[])
+        {
             Ok(u) => u,
             Err(e) => return Err(e),
         },
@@ -510,6 +735,7 @@ fn to_avro_schema(
             id: registered_schema.id,
             raw: registered_schema.schema,
             parsed,
+            etag: None,
         }),
         Err(e) => Err(SRCError::non_retryable_with_cause(
             e,
@@ -524,10 +750,10 @@ fn to_avro_schema(
 #[cfg(test)]
 mod tests {
     use avro_rs::from_value;
-    use mockito::{mock, server_address};
+    use mockito::{mock, server_address, Matcher};
 
     use crate::avro_common::get_supplied_schema;
-    use crate::schema_registry_common::SuppliedSchema;
+    use crate::schema_registry_common::{FieldDescriptor, SuppliedSchema};
 
     use super::*;
     use test_utils::Heartbeat;
@@ -537,7 +763,7 @@ mod tests {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let decoder = AvroDecoder::new(sr_settings);
         assert_eq!(
-            "AvroDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None }, cache: {} }"
+            "AvroDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None, max_reference_depth: 64, content_types: {}, deadline: None, retry_statuses: {}, read_only: false, query_params: [], schema_transform: None, retry_policy: None, register_only_new_versions: false }, cache: {}, counters: CacheCounters { hits: 0, misses: 0, evictions: 0 }, compression: None }"
                 .to_owned(),
             format!("{:?}", decoder)
         )
@@ -748,6 +974,108 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_all() {
+        let _m = mock("GET", "/schemas/ids/1?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let heartbeats = decoder
+            .decode_all(&[0, 0, 0, 0, 1, 6, 0, 0, 0, 0, 1, 8])
+            .unwrap();
+
+        let values: Vec<Value> = heartbeats.into_iter().map(|r| r.value).collect();
+        assert_eq!(
+            values,
+            vec![
+                Value::Record(vec![("beat".to_string(), Value::Long(3))]),
+                Value::Record(vec![("beat".to_string(), Value::Long(4))])
+            ]
+        );
+        let err = decoder
+            .decode_all(&[0, 0, 0, 0, 1, 6, 0, 0, 0])
+            .unwrap_err();
+        assert_eq!(
+            err.error,
+            "Bytes from position 6 don't form a complete message"
+        )
+    }
+
+    #[test]
+    fn test_decode_with_field_types() {
+        let _m = mock("GET", "/schemas/ids/1?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"},{\"name\":\"note\",\"type\":[\"null\",\"string\"]}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let described = decoder
+            .decode_with_field_types(Some(&[0, 0, 0, 0, 1, 6, 0]))
+            .unwrap();
+
+        assert_eq!(
+            described.fields,
+            vec![
+                FieldDescriptor {
+                    name: String::from("beat"),
+                    type_name: String::from("long"),
+                    nullable: false,
+                    repeated: false,
+                },
+                FieldDescriptor {
+                    name: String::from("note"),
+                    type_name: String::from("string"),
+                    nullable: true,
+                    repeated: false,
+                },
+            ]
+        );
+        assert_eq!(
+            described.value.value,
+            Value::Record(vec![
+                ("beat".to_string(), Value::Long(3)),
+                ("note".to_string(), Value::Union(Box::new(Value::Null)))
+            ])
+        )
+    }
+
+    #[test]
+    fn test_decode_batch() {
+        let _m = mock("GET", "/schemas/ids/1?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let valid: &[u8] = &[0, 0, 0, 0, 1, 6];
+        let invalid: &[u8] = &[1, 2];
+        let batch = decoder
+            .decode_batch(&[Some(valid), None, Some(invalid)])
+            .unwrap();
+
+        assert_eq!(batch.decoded.len(), 1);
+        assert_eq!(batch.decoded[0].0, 0);
+        assert_eq!(
+            batch.decoded[0].1.value,
+            Value::Record(vec![("beat".to_string(), Value::Long(3))])
+        );
+        assert_eq!(
+            batch.skipped,
+            vec![
+                (1, BytesResult::Null),
+                (2, BytesResult::Invalid(vec![1, 2]))
+            ]
+        )
+    }
+
     #[test]
     fn test_decoder_default_no_schema_in_response() {
         let _m = mock("GET", "/schemas/ids/1?deleted=true")
@@ -832,10 +1160,7 @@ mod tests {
             .with_body(r#"{"error_code":40403,"message":"Schema not found"}"#)
             .create();
         let heartbeat = decoder.decode(Some(&bytes));
-        assert_eq!(
-            heartbeat,
-            Err(SRCError::new("Could not get raw schema from response", None, false).into_cache())
-        );
+        assert_eq!(heartbeat, Err(SRCError::schema_not_found(2).into_cache()));
         let _m = mock("GET", "/schemas/ids/2?deleted=true")
             .with_status(200)
             .with_header("content-type", "application/vnd.schemaregistry.v1+json")
@@ -843,10 +1168,7 @@ mod tests {
             .create();
 
         let heartbeat = decoder.decode(Some(&bytes));
-        assert_eq!(
-            heartbeat,
-            Err(SRCError::new("Could not get raw schema from response", None, false).into_cache())
-        );
+        assert_eq!(heartbeat, Err(SRCError::schema_not_found(2).into_cache()));
 
         decoder.remove_errors_from_cache();
 
@@ -857,12 +1179,45 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_decoder_cache_stats() {
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = AvroDecoder::new(sr_settings);
+        let bytes = [0, 0, 0, 0, 2, 6];
+
+        let _m = mock("GET", "/schemas/ids/2?deleted=true")
+            .with_status(404)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"error_code":40403,"message":"Schema not found"}"#)
+            .create();
+        decoder.decode(Some(&bytes)).unwrap_err();
+        decoder.remove_errors_from_cache();
+
+        let _m = mock("GET", "/schemas/ids/2?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+        decoder.decode(Some(&bytes)).unwrap();
+        decoder.decode(Some(&bytes)).unwrap();
+
+        assert_eq!(
+            decoder.cache_stats(),
+            CacheStats {
+                entries: 1,
+                hits: 1,
+                misses: 2,
+                evictions: 1,
+            }
+        )
+    }
+
     #[test]
     fn display_encode() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let encoder = AvroEncoder::new(sr_settings);
         assert_eq!(
-            "AvroEncoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None }, cache: {} }"
+            "AvroEncoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None, max_reference_depth: 64, content_types: {}, deadline: None, retry_statuses: {}, read_only: false, query_params: [], schema_transform: None, retry_policy: None, register_only_new_versions: false }, cache: {}, compression: None }"
                 .to_owned(),
             format!("{:?}", encoder)
         )
@@ -905,6 +1260,47 @@ mod tests {
         assert_eq!(bytes, Ok(vec![0, 0, 0, 0, 3, 6]))
     }
 
+    #[test]
+    fn test_refresh_latest() {
+        let _m = mock("GET", "/subjects/refresh-value/versions/latest")
+            .match_header("if-none-match", Matcher::Missing)
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_header("etag", "\"v1\"")
+            .with_body(r#"{"subject":"refresh-value","version":1,"id":3,"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .expect(1)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut encoder = AvroEncoder::new(sr_settings);
+        let strategy = SubjectNameStrategy::TopicNameStrategy(String::from("refresh"), false);
+        let bytes = encoder.encode(vec![("beat", Value::Long(3))], &strategy);
+        assert_eq!(bytes, Ok(vec![0, 0, 0, 0, 3, 6]));
+
+        let not_modified = mock("GET", "/subjects/refresh-value/versions/latest")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(304)
+            .expect(1)
+            .create();
+        assert_eq!(encoder.refresh_latest(), Ok(()));
+        not_modified.assert();
+        drop(not_modified);
+        let bytes = encoder.encode(vec![("beat", Value::Long(3))], &strategy);
+        assert_eq!(bytes, Ok(vec![0, 0, 0, 0, 3, 6]));
+
+        let _n = mock("GET", "/subjects/refresh-value/versions/latest")
+            .match_header("if-none-match", "\"v1\"")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_header("etag", "\"v2\"")
+            .with_body(r#"{"subject":"refresh-value","version":3,"id":5,"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+            .create();
+        assert_eq!(encoder.refresh_latest(), Ok(()));
+        let bytes = encoder.encode(vec![("beat", Value::Long(3))], &strategy);
+        assert_eq!(bytes, Ok(vec![0, 0, 0, 0, 5, 6]));
+        _m.assert();
+    }
+
     #[test]
     fn test_using_record_name() {
         let _m = mock("GET", "/subjects/heartbeat-nl.openweb.data.Heartbeat/versions/latest")
@@ -1021,7 +1417,14 @@ mod tests {
         let bytes = encoder.encode(vec![("beat", Value::Long(3))], &strategy);
         assert_eq!(
             bytes,
-            Err(SRCError::new("Could not get id from response", None, false).into_cache())
+            Err(SRCError::new(
+                "schema registry responded with error code 40403",
+                Some(String::from("Schema not found")),
+                false,
+            )
+            .with_status(404)
+            .with_registry_error(40403, "Schema not found")
+            .into_cache())
         );
 
         let _n = mock("GET", "/subjects/nl.openweb.data.Heartbeat/versions/latest")
@@ -1033,10 +1436,14 @@ mod tests {
         let bytes = encoder.encode(vec![("beat", Value::Long(3))], &strategy);
         assert_eq!(
             bytes,
-            Err(
-                SRCError::non_retryable_without_cause("Could not get id from response")
-                    .into_cache()
+            Err(SRCError::new(
+                "schema registry responded with error code 40403",
+                Some(String::from("Schema not found")),
+                false,
             )
+            .with_status(404)
+            .with_registry_error(40403, "Schema not found")
+            .into_cache())
         );
 
         encoder.remove_errors_from_cache();
@@ -1201,14 +1608,11 @@ mod tests {
         let error = encoder.encode(vec![("beat", Value::Long(3))], &strategy);
         assert_eq!(
             error,
-            Err(SRCError::new(
-                "could not parse to RawRegisteredSchema, schema might not exist on this schema registry, the http call failed, cause will give more information",
-                Some(String::from(
-                    "error decoding response body: EOF while parsing a value at line 1 column 0"
-                )),
-                false,
+            Err(
+                SRCError::new("schema registry responded with status 501", None, false)
+                    .with_status(501)
+                    .into_cache()
             )
-            .into_cache())
         )
     }
 
@@ -1216,6 +1620,7 @@ mod tests {
     fn error_when_invalid_schema() {
         let registered_schema = RegisteredSchema {
             id: 0,
+            version: None,
             schema_type: SchemaType::Avro,
             schema: String::from(r#"{"type":"record","name":"Name"}"#),
             references: vec![],
@@ -1232,6 +1637,7 @@ mod tests {
     fn error_when_invalid_type() {
         let registered_schema = RegisteredSchema {
             id: 0,
+            version: None,
             schema_type: SchemaType::Protobuf,
             schema: String::from(
                 r#"syntax = "proto3"; package org.schema_registry_test_app.proto; message Result { string up = 1; string down = 2; }"#,
@@ -11,10 +11,13 @@ use crate::blocking::schema_registry::{
     get_referenced_schema, get_schema_by_id_and_type, get_schema_by_subject, SrSettings,
 };
 use crate::error::SRCError;
-use crate::json_common::{fetch_fallback, fetch_id, handle_validation, to_bytes, to_value};
+use crate::json_common::{
+    content_hash, fetch_fallback, fetch_id, handle_validation, to_bytes, to_value,
+};
 use crate::schema_registry_common::{
-    get_bytes_result, get_subject, BytesResult, RegisteredReference, RegisteredSchema, SchemaType,
-    SubjectNameStrategy,
+    extend_reference_path, get_bytes_result, get_id_and_data, get_subject, BytesResult,
+    CacheCounters, CacheStats, PayloadCompression, RegisteredReference, RegisteredSchema,
+    SchemaType, SubjectNameStrategy,
 };
 
 /// Encoder that works by prepending the correct bytes in order to make it valid schema registry
@@ -26,6 +29,7 @@ pub struct JsonEncoder {
     sr_settings: SrSettings,
     cache: HashMap<String, Result<EncodeContext, SRCError>, RandomState>,
     scope: Scope,
+    validate_on_encode: bool,
 }
 
 impl JsonEncoder {
@@ -35,8 +39,15 @@ impl JsonEncoder {
             sr_settings,
             cache: HashMap::new(),
             scope: Scope::new(),
+            validate_on_encode: true,
         }
     }
+    /// Sets whether the value is validated against the schema before encoding, when the value isn't
+    /// valid a non-retryable error with the validation errors is returned. By default values are
+    /// validated.
+    pub fn set_validate_on_encode(&mut self, validate_on_encode: bool) {
+        self.validate_on_encode = validate_on_encode;
+    }
     /// Removes errors from the cache, can be useful to retry failed encodings.
     pub fn remove_errors_from_cache(&mut self) {
         self.cache.retain(|_, v| v.is_ok());
@@ -50,7 +61,9 @@ impl JsonEncoder {
     ) -> Result<Vec<u8>, SRCError> {
         let key = get_subject(subject_name_strategy)?;
         let (validation, id) = self.validate(key, subject_name_strategy, value)?;
-        handle_validation(validation, value)?;
+        if let Some(validation) = validation {
+            handle_validation(validation, value)?;
+        }
         to_bytes(id, value)
     }
 
@@ -59,7 +72,8 @@ impl JsonEncoder {
         key: String,
         subject_name_strategy: &SubjectNameStrategy,
         value: &Value,
-    ) -> Result<(ValidationState, u32), SRCError> {
+    ) -> Result<(Option<ValidationState>, u32), SRCError> {
+        let validate_on_encode = self.validate_on_encode;
         let cached_context = match self.cache.entry(key) {
             Entry::Occupied(e) => e.into_mut().as_ref(),
             Entry::Vacant(e) => {
@@ -82,7 +96,10 @@ impl JsonEncoder {
         };
         match cached_context {
             Ok(context) => match self.scope.resolve(&context.url) {
-                Some(schema) => Ok((schema.validate(value), context.id)),
+                Some(schema) if validate_on_encode => {
+                    Ok((Some(schema.validate(value)), context.id))
+                }
+                Some(_) => Ok((None, context.id)),
                 None => Err(SRCError::non_retryable_without_cause(
                     "could not get schema from scope",
                 )),
@@ -98,11 +115,17 @@ struct EncodeContext {
     url: Url,
 }
 
+/// Decodes bytes into a serde_json Value. By default numbers are parsed into either an i64, u64 or
+/// f64, so large decimals lose precision. With the `json_arbitrary_precision` feature numbers keep
+/// the exact textual representation, which is also used when encoding the value again.
 #[derive(Debug)]
 pub struct JsonDecoder {
     sr_settings: SrSettings,
     cache: HashMap<u32, Result<Url, SRCError>, RandomState>,
     scope: Scope,
+    counters: CacheCounters,
+    compression: PayloadCompression,
+    validate_on_decode: bool,
 }
 
 impl JsonDecoder {
@@ -115,32 +138,87 @@ impl JsonDecoder {
         JsonDecoder {
             sr_settings,
             cache: HashMap::new(),
+            counters: CacheCounters::default(),
+            compression: PayloadCompression::None,
+            validate_on_decode: false,
             scope: Scope::new(),
         }
     }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<JsonDecoder, SRCError> {
+        SrSettings::global().map(JsonDecoder::new)
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
     pub fn remove_errors_from_cache(&mut self) {
+        let before = self.cache.len();
         self.cache.retain(|_, v| v.is_ok());
+        self.counters.evicted(before - self.cache.len());
+    }
+    /// Gives a snapshot of the statistics of the cache, like the number of entries, and how often a
+    /// schema could be taken from the cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.counters.to_stats(self.cache.len())
+    }
+    /// Sets the compression of the bytes after the header, which will be decompressed before
+    /// decoding. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
+    }
+    /// Sets whether the decoded value is validated against the schema, when the value isn't valid a
+    /// non-retryable error with the validation errors is returned. By default values are not
+    /// validated.
+    pub fn set_validate_on_decode(&mut self, validate_on_decode: bool) {
+        self.validate_on_decode = validate_on_decode;
     }
     /// Reads the bytes to get the name, and gives back the data bytes.
     pub fn decode(&mut self, bytes: Option<&[u8]>) -> Result<Option<DecodeResult>, SRCError> {
         match get_bytes_result(bytes) {
             BytesResult::Null => Ok(None),
-            BytesResult::Valid(id, bytes) => Ok(Some(self.deserialize(id, &bytes)?)),
+            BytesResult::Valid(id, bytes) => Ok(Some(
+                self.deserialize(id, &self.compression.decompress(bytes)?)?,
+            )),
             BytesResult::Invalid(i) => Err(SRCError::non_retryable_without_cause(&*format!(
                 "Invalid bytes: {:?}",
                 i
             ))),
         }
     }
+    /// Decodes bytes like decode, and also gives the schema id from the bytes, for example to log
+    /// which schema was used. Null bytes, or bytes without valid framing, give an error.
+    pub fn decode_with_id(&mut self, bytes: Option<&[u8]>) -> Result<(u32, Value), SRCError> {
+        let (id, data) = get_id_and_data(bytes)?;
+        let value = self
+            .deserialize(id, &self.compression.decompress(data)?)?
+            .value;
+        Ok((id, value))
+    }
+    /// Decodes bytes like decode, and also gives a hash of the decoded content. Since the keys are
+    /// hashed in sorted order, the hash doesn't depend on the order of the keys in the json, and
+    /// can be used to detect duplicate messages.
+    pub fn decode_and_hash(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<Option<(Value, u64)>, SRCError> {
+        Ok(self.decode(bytes)?.map(|result| {
+            let hash = content_hash(&result.value);
+            (result.value, hash)
+        }))
+    }
     /// The actual deserialization trying to get the id from the bytes to retrieve the schema, and
     /// using a reader transforms the bytes to a value.
     fn deserialize(&mut self, id: u32, bytes: &[u8]) -> Result<DecodeResult, SRCError> {
+        let validate_on_decode = self.validate_on_decode;
         let schema = self.get_schema(id)?;
         match serde_json::from_slice(bytes) {
-            Ok(value) => Ok(DecodeResult { schema, value }),
+            Ok(value) => {
+                if validate_on_decode {
+                    handle_validation(schema.validate(&value), &value)?;
+                }
+                Ok(DecodeResult { schema, value })
+            }
             Err(e) => Err(SRCError::non_retryable_with_cause(
                 e,
                 "could not create value from bytes",
@@ -151,8 +229,12 @@ impl JsonDecoder {
     /// it into the cache.
     fn get_schema(&mut self, id: u32) -> Result<ScopedSchema, SRCError> {
         let url = match self.cache.entry(id) {
-            Entry::Occupied(e) => &*e.into_mut(),
+            Entry::Occupied(e) => {
+                self.counters.hit();
+                &*e.into_mut()
+            }
             Entry::Vacant(e) => {
+                self.counters.miss();
                 let v = match get_schema_by_id_and_type(id, &self.sr_settings, SchemaType::Json) {
                     Ok(r) => match set_scoped_schema(&mut self.scope, &self.sr_settings, &r) {
                         Ok(schema) => Ok(schema),
@@ -173,14 +255,31 @@ impl JsonDecoder {
             Err(e) => Err(e.clone()),
         }
     }
+    #[cfg(all(feature = "avro", feature = "proto_decoder"))]
+    /// Puts a schema that was already fetched, for example to detect the schema type, in the cache,
+    /// so it's not fetched again when decoding.
+    pub(crate) fn add_to_cache(&mut self, id: u32, registered_schema: RegisteredSchema) {
+        if let Entry::Vacant(e) = self.cache.entry(id) {
+            self.counters.miss();
+            let v = match set_scoped_schema(&mut self.scope, &self.sr_settings, &registered_schema)
+            {
+                Ok(schema) => Ok(schema),
+                Err(e) => Err(e.into_cache()),
+            };
+            e.insert(v);
+        }
+    }
 }
 
 fn add_refs_to_scope(
     scope: &mut Scope,
     sr_settings: &SrSettings,
     refs: &[RegisteredReference],
+    path: &[String],
 ) -> Result<(), SRCError> {
     for rr in refs.iter() {
+        let child_path =
+            extend_reference_path(path, &rr.subject, sr_settings.max_reference_depth())?;
         let rs = get_referenced_schema(sr_settings, rr)?;
         let id = match Url::from_str(&*rr.name) {
             Ok(v) => v,
@@ -190,7 +289,7 @@ fn add_refs_to_scope(
         if scope.resolve(&id).is_some() {
             return Ok(());
         }
-        add_refs_to_scope(scope, sr_settings, &rs.references)?;
+        add_refs_to_scope(scope, sr_settings, &rs.references, &child_path)?;
         let def: Value = to_value(&*rs.schema)?;
         scope.compile_with_id(&id, def, false).unwrap();
     }
@@ -202,7 +301,7 @@ fn set_scoped_schema(
     sr_settings: &SrSettings,
     registered_schema: &RegisteredSchema,
 ) -> Result<Url, SRCError> {
-    add_refs_to_scope(scope, sr_settings, &registered_schema.references)?;
+    add_refs_to_scope(scope, sr_settings, &registered_schema.references, &[])?;
     let def: Value = match serde_json::from_str(&*registered_schema.schema) {
         Ok(v) => v,
         Err(e) => {
@@ -645,7 +744,7 @@ mod tests {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let encoder = JsonEncoder::new(sr_settings);
         assert_eq!(true,
-            format!("{:?}", encoder).starts_with("JsonEncoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None }, cache: {}, scope: Scope {")
+            format!("{:?}", encoder).starts_with("JsonEncoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None, max_reference_depth: 64, content_types: {}, deadline: None, retry_statuses: {}, read_only: false, query_params: [], schema_transform: None, retry_policy: None, register_only_new_versions: false }, cache: {}, scope: Scope {")
         )
     }
 
@@ -654,10 +753,29 @@ mod tests {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let decoder = JsonDecoder::new(sr_settings);
         assert_eq!(true,
-                   format!("{:?}", decoder).starts_with("JsonDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None }, cache: {}, scope: Scope {")
+                   format!("{:?}", decoder).starts_with("JsonDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None, max_reference_depth: 64, content_types: {}, deadline: None, retry_statuses: {}, read_only: false, query_params: [], schema_transform: None, retry_policy: None, register_only_new_versions: false }, cache: {}, scope: Scope {")
         )
     }
 
+    #[test]
+    fn test_encode_without_validation() {
+        let _m = mock("GET", "/subjects/testresult-value/versions/latest")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_json_body(json_result_schema(), 10))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut encoder = JsonEncoder::new(sr_settings);
+        encoder.set_validate_on_encode(false);
+        let strategy = SubjectNameStrategy::TopicNameStrategy(String::from("testresult"), false);
+        let result_example: Value = Value::String(String::from("Foo"));
+
+        let bytes = encoder.encode(&result_example, &strategy).unwrap();
+
+        assert_eq!(bytes, get_payload(10, br#""Foo""#.to_vec()))
+    }
+
     #[test]
     fn test_encode_not_valid() {
         let _m = mock("GET", "/subjects/testresult-value/versions/latest")
@@ -706,6 +824,24 @@ mod tests {
         )
     }
 
+    #[test]
+    fn decode_with_validation() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_json_body(json_result_schema(), 7))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = JsonDecoder::new(sr_settings);
+        decoder.set_validate_on_decode(true);
+        let error = decoder
+            .decode(Some(&get_payload(7, br#"{"up":1}"#.to_vec())))
+            .unwrap_err();
+
+        assert!(error.error.starts_with(r#"Value {"up":1} was not valid"#))
+    }
+
     #[test]
     fn decode_invalid_bytes() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
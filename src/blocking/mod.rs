@@ -2,6 +2,8 @@
 pub mod avro;
 #[cfg(feature = "json")]
 pub mod json;
+#[cfg(all(feature = "avro", feature = "json", feature = "proto_decoder"))]
+pub mod multi_decoder;
 #[cfg(feature = "proto_decoder")]
 pub mod proto_decoder;
 #[cfg(feature = "proto_raw")]
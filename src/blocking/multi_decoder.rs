@@ -0,0 +1,167 @@
+//! Decoder that can decode bytes of any of the supported schema types, for example for topics that
+//! contain records of multiple types. Based on the schema type of the id encoded in the bytes, the
+//! bytes are decoded with either the Avro, the Json or the Protobuf decoder.
+use crate::avro_common::DecodeResult;
+use crate::blocking::avro::AvroDecoder;
+use crate::blocking::json::JsonDecoder;
+use crate::blocking::proto_decoder::ProtoDecoder;
+use crate::blocking::schema_registry::{SchemaTypeDetector, SrSettings};
+use crate::error::SRCError;
+use crate::schema_registry_common::{get_bytes_result, BytesResult, DecodeCounts, SchemaType};
+
+/// The decoded value, which depends on the schema type the bytes were encoded with.
+#[derive(Debug)]
+pub enum MultiDecodedValue {
+    Null,
+    Avro(DecodeResult),
+    Json(serde_json::Value),
+    Protobuf(protofish::Value),
+}
+
+/// Holds a decoder for each of the supported schema types, and uses the schema type of the id in
+/// the bytes to pick the one to use. The schema type is cached per id, just like the schema's are
+/// cached in the separate decoders. The schema fetched to detect the type is also put in the cache
+/// of the decoder that is picked, so each schema is only fetched once.
+///
+/// ```
+/// use mockito::{mock, server_address};
+/// use avro_rs::types::Value;
+/// use schema_registry_converter::blocking::schema_registry::SrSettings;
+/// use schema_registry_converter::blocking::multi_decoder::{MultiDecodedValue, MultiDecoder};
+///
+/// let _m = mock("GET", "/schemas/ids/1?deleted=true")
+///     .with_status(200)
+///     .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+///     .with_body(r#"{"schema":"{\"type\":\"record\",\"name\":\"Heartbeat\",\"namespace\":\"nl.openweb.data\",\"fields\":[{\"name\":\"beat\",\"type\":\"long\"}]}"}"#)
+///     .create();
+///
+/// let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+/// let mut decoder = MultiDecoder::new(sr_settings);
+/// match decoder.decode(Some(&[0, 0, 0, 0, 1, 6])).unwrap() {
+///     MultiDecodedValue::Avro(r) => assert_eq!(r.value, Value::Record(vec![("beat".to_string(), Value::Long(3))])),
+///     v => panic!("Other value: {:?} than expected Avro", v),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MultiDecoder {
+    detector: SchemaTypeDetector,
+    avro_decoder: AvroDecoder,
+    json_decoder: JsonDecoder,
+    proto_decoder: ProtoDecoder,
+    counts: Option<DecodeCounts>,
+}
+
+impl MultiDecoder {
+    /// Creates a new decoder, all the decoders used will use the same sr settings.
+    pub fn new(sr_settings: SrSettings) -> MultiDecoder {
+        MultiDecoder {
+            detector: SchemaTypeDetector::new(sr_settings.clone()),
+            avro_decoder: AvroDecoder::new(sr_settings.clone()),
+            json_decoder: JsonDecoder::new(sr_settings.clone()),
+            proto_decoder: ProtoDecoder::new(sr_settings),
+            counts: None,
+        }
+    }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<MultiDecoder, SRCError> {
+        SrSettings::global().map(MultiDecoder::new)
+    }
+    /// Remove al the errors from the cache of the detector and all the decoders.
+    pub fn remove_errors_from_cache(&mut self) {
+        self.detector.remove_errors_from_cache();
+        self.avro_decoder.remove_errors_from_cache();
+        self.json_decoder.remove_errors_from_cache();
+        self.proto_decoder.remove_errors_from_cache();
+    }
+    /// Starts counting the decoded messages per schema type and per schema id, by default they are
+    /// not counted.
+    pub fn enable_decode_counts(&mut self) {
+        if self.counts.is_none() {
+            self.counts = Some(DecodeCounts::default())
+        }
+    }
+    /// Gives a snapshot of the number of decoded messages, when counting is enabled.
+    pub fn decode_counts(&self) -> Option<DecodeCounts> {
+        self.counts.clone()
+    }
+    /// Decodes the bytes with the decoder matching the schema type.
+    pub fn decode(&mut self, bytes: Option<&[u8]>) -> Result<MultiDecodedValue, SRCError> {
+        if bytes.is_none() {
+            return Ok(MultiDecodedValue::Null);
+        }
+        let (schema_type, registered_schema) = self.detector.detect_schema(bytes)?;
+        if let (Some(registered_schema), BytesResult::Valid(id, _)) =
+            (registered_schema, get_bytes_result(bytes))
+        {
+            match schema_type {
+                SchemaType::Avro => self.avro_decoder.add_to_cache(id, registered_schema),
+                SchemaType::Json => self.json_decoder.add_to_cache(id, registered_schema),
+                SchemaType::Protobuf => self.proto_decoder.add_to_cache(id, registered_schema),
+                SchemaType::Other(_) => (),
+            }
+        }
+        let value = match &schema_type {
+            SchemaType::Avro => MultiDecodedValue::Avro(self.avro_decoder.decode(bytes)?),
+            SchemaType::Json => match self.json_decoder.decode(bytes)? {
+                None => MultiDecodedValue::Null,
+                Some(r) => MultiDecodedValue::Json(r.value),
+            },
+            SchemaType::Protobuf => MultiDecodedValue::Protobuf(self.proto_decoder.decode(bytes)?),
+            SchemaType::Other(t) => {
+                return Err(SRCError::non_retryable_without_cause(&format!(
+                    "schema type {} is not supported",
+                    t
+                )))
+            }
+        };
+        if let (Some(counts), BytesResult::Valid(id, _)) =
+            (&mut self.counts, get_bytes_result(bytes))
+        {
+            *counts.per_type.entry(schema_type).or_insert(0) += 1;
+            *counts.per_id.entry(id).or_insert(0) += 1;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{mock, server_address};
+
+    use crate::blocking::multi_decoder::{MultiDecodedValue, MultiDecoder};
+    use crate::blocking::schema_registry::SrSettings;
+    use test_utils::{get_proto_body, get_proto_hb_101, get_proto_hb_schema};
+
+    #[test]
+    fn decode_null() {
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = MultiDecoder::new(sr_settings);
+
+        let result = decoder.decode(None).unwrap();
+
+        assert!(matches!(result, MultiDecodedValue::Null))
+    }
+
+    #[test]
+    fn decode_protobuf() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 7))
+            .expect(1)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = MultiDecoder::new(sr_settings);
+
+        let result = decoder.decode(Some(get_proto_hb_101())).unwrap();
+
+        let message = match result {
+            MultiDecodedValue::Protobuf(protofish::Value::Message(x)) => *x,
+            v => panic!("Other value: {:?} than expected Protobuf", v),
+        };
+        assert_eq!(protofish::Value::UInt64(101u64), message.fields[0].value);
+        _m.assert()
+    }
+}
@@ -1,20 +1,31 @@
 use std::collections::hash_map::{Entry, RandomState};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use bytes::Bytes;
 use protofish::{Context, MessageValue, Value};
 
 use crate::blocking::schema_registry::{
-    get_referenced_schema, get_schema_by_id_and_type, SrSettings,
+    get_referenced_schema, get_schema_by_id_and_type, get_schema_by_subject, SrSettings,
 };
 use crate::error::SRCError;
+use crate::proto_decoder_common::{
+    content_hash, convert_well_known_types, describe_message, remove_corrupt_fields,
+    take_unknown_fields, to_decoded_value, ConvertedValue, DecodedValue, UnknownField,
+};
 use crate::proto_resolver::{resolve_name, to_index_and_data, MessageResolver};
-use crate::schema_registry_common::{get_bytes_result, BytesResult, RegisteredSchema, SchemaType};
+use crate::schema_registry_common::{
+    extend_reference_path, get_bytes_result, get_id_and_data, get_sliced_bytes_result, BatchResult,
+    BytesResult, CacheCounters, CacheStats, DescribedValue, PayloadCompression, RegisteredSchema,
+    SchemaType, SlicedBytesResult, SubjectNameStrategy,
+};
 
 #[derive(Debug)]
 pub struct ProtoDecoder {
     sr_settings: SrSettings,
     cache: HashMap<u32, Result<DecodeContext, SRCError>, RandomState>,
+    counters: CacheCounters,
+    compression: PayloadCompression,
+    preloaded_references: HashMap<String, String>,
 }
 
 impl ProtoDecoder {
@@ -27,13 +38,40 @@ impl ProtoDecoder {
         ProtoDecoder {
             sr_settings,
             cache: HashMap::new(),
+            counters: CacheCounters::default(),
+            compression: PayloadCompression::None,
+            preloaded_references: HashMap::new(),
         }
     }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<ProtoDecoder, SRCError> {
+        SrSettings::global().map(ProtoDecoder::new)
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
     pub fn remove_errors_from_cache(&mut self) {
+        let before = self.cache.len();
         self.cache.retain(|_, v| v.is_ok());
+        self.counters.evicted(before - self.cache.len());
+    }
+    /// Gives a snapshot of the statistics of the cache, like the number of entries, and how often a
+    /// schema could be taken from the cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.counters.to_stats(self.cache.len())
+    }
+    /// Sets the compression of the bytes after the header, which will be decompressed before
+    /// decoding. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
+    }
+    /// Sets schema's, keyed by the name of the reference, like the import path, which will be used
+    /// for references instead of fetching them from the schema registry. The supplied schema's
+    /// can't have references themselves. Should be set before decoding, since already resolved
+    /// schema's are cached.
+    pub fn set_preloaded_references(&mut self, preloaded_references: HashMap<String, String>) {
+        self.preloaded_references = preloaded_references;
     }
     /// Decodes bytes into a value.
     /// The choice to use Option<&[u8]> as type us made so it plays nice with the BorrowedMessage
@@ -44,20 +82,233 @@ impl ProtoDecoder {
         match get_bytes_result(bytes) {
             BytesResult::Null => Ok(Value::Bytes(Bytes::new())),
             BytesResult::Valid(id, bytes) => {
-                Ok(Value::Message(Box::from(self.deserialize(id, &bytes)?)))
+                self.deserialize(id, &self.compression.decompress(bytes)?, |_, m| {
+                    Value::Message(Box::from(m))
+                })
+            }
+            BytesResult::Invalid(i) => Ok(Value::Bytes(Bytes::from(i))),
+        }
+    }
+    /// Decodes bytes like decode, but from `Bytes`, like they are held by some Kafka clients. The
+    /// message is sliced from the bytes instead of copied, unless the payload is compressed.
+    pub fn decode_bytes(&mut self, bytes: Option<&Bytes>) -> Result<Value, SRCError> {
+        match get_sliced_bytes_result(bytes) {
+            SlicedBytesResult::Null => Ok(Value::Bytes(Bytes::new())),
+            SlicedBytesResult::Valid(id, data) => {
+                let data = if self.compression == PayloadCompression::None {
+                    data
+                } else {
+                    Bytes::from(self.compression.decompress(data.to_vec())?)
+                };
+                self.deserialize(id, &data, |_, m| Value::Message(Box::from(m)))
+            }
+            SlicedBytesResult::Invalid(i) => Ok(Value::Bytes(i)),
+        }
+    }
+    /// Decodes a batch of records, only the records with valid framing are decoded, null and invalid
+    /// records are returned as skipped, so they can be handled separately. The batch is aborted
+    /// when one of the valid records can't be decoded.
+    pub fn decode_batch(
+        &mut self,
+        items: &[Option<&[u8]>],
+    ) -> Result<BatchResult<Value>, SRCError> {
+        let mut result = BatchResult {
+            decoded: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for (index, bytes) in items.iter().enumerate() {
+            match get_bytes_result(*bytes) {
+                BytesResult::Valid(id, bytes) => {
+                    let value =
+                        self.deserialize(id, &self.compression.decompress(bytes)?, |_, m| {
+                            Value::Message(Box::from(m))
+                        })?;
+                    result.decoded.push((index, value))
+                }
+                skipped => result.skipped.push((index, skipped)),
+            }
+        }
+        Ok(result)
+    }
+    /// Decodes bytes like decode, but converts the well-known types Timestamp, Duration and Struct
+    /// to more convenient types. Other well-known types are kept as message.
+    pub fn decode_with_well_known_types(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<ConvertedValue, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(ConvertedValue::Other(Value::Bytes(Bytes::new()))),
+            BytesResult::Valid(id, bytes) => self.deserialize(
+                id,
+                &self.compression.decompress(bytes)?,
+                convert_well_known_types,
+            ),
+            BytesResult::Invalid(i) => Ok(ConvertedValue::Other(Value::Bytes(Bytes::from(i)))),
+        }
+    }
+    /// Decodes bytes like decode, but into a value owned by this crate, so protofish is not needed
+    /// to use the result. Messages become maps with the names of the fields as keys.
+    pub fn decode_value(&mut self, bytes: Option<&[u8]>) -> Result<DecodedValue, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(DecodedValue::Null),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, to_decoded_value)
+            }
+            BytesResult::Invalid(i) => Ok(DecodedValue::Bytes(i)),
+        }
+    }
+    /// Decodes the bytes with the latest schema of the subject, instead of the schema of the id in
+    /// the bytes, the message index in the bytes is resolved against the latest schema. This is
+    /// meant to check whether data can be read with the latest schema, for normal decoding use
+    /// decode. The latest schema is not cached.
+    pub fn decode_with_subject_latest(
+        &mut self,
+        subject_name_strategy: &SubjectNameStrategy,
+        bytes: Option<&[u8]>,
+    ) -> Result<Value, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(Value::Bytes(Bytes::new())),
+            BytesResult::Valid(_, bytes) => {
+                let registered_schema =
+                    get_schema_by_subject(&self.sr_settings, subject_name_strategy)?;
+                if registered_schema.schema_type != SchemaType::Protobuf {
+                    return Err(SRCError::non_retryable_without_cause(&format!(
+                        "type {:?}, is not correct",
+                        registered_schema.schema_type
+                    )));
+                }
+                let context = to_resolve_context(
+                    &self.sr_settings,
+                    &self.preloaded_references,
+                    registered_schema,
+                )?;
+                let bytes = self.compression.decompress(bytes)?;
+                let (index, data) = to_index_and_data(&bytes);
+                let full_name = resolve_name(&context.resolver, &index)?;
+                let message_info = context.context.get_message(full_name).unwrap();
+                Ok(Value::Message(Box::from(
+                    message_info.decode(data, &context.context),
+                )))
             }
             BytesResult::Invalid(i) => Ok(Value::Bytes(Bytes::from(i))),
         }
     }
+    /// Decodes bytes like decode, and also gives the schema id from the bytes, for example to log
+    /// which schema was used. Null bytes, or bytes without valid framing, give an error.
+    pub fn decode_with_id(&mut self, bytes: Option<&[u8]>) -> Result<(u32, Value), SRCError> {
+        let (id, data) = get_id_and_data(bytes)?;
+        let value = self.deserialize(id, &self.compression.decompress(data)?, |_, m| {
+            Value::Message(Box::from(m))
+        })?;
+        Ok((id, value))
+    }
+    /// Decodes bytes like decode, but also gives the full name of the message, as selected by the
+    /// message indexes after the schema id. When the bytes are null or not framed, the name is empty
+    /// and the value is the same as for decode.
+    pub fn decode_with_name(&mut self, bytes: Option<&[u8]>) -> Result<(String, Value), SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok((String::new(), Value::Bytes(Bytes::new()))),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, |context, m| {
+                    let name = context.resolve_message(m.msg_ref).full_name.clone();
+                    (name, Value::Message(Box::from(m)))
+                })
+            }
+            BytesResult::Invalid(i) => Ok((String::new(), Value::Bytes(Bytes::from(i)))),
+        }
+    }
+    /// Decodes bytes like decode, but also gives the declared types of the fields of the message,
+    /// as derived from the proto schema.
+    pub fn decode_with_field_types(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<DescribedValue<Value>, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(DescribedValue {
+                value: Value::Bytes(Bytes::new()),
+                fields: Vec::new(),
+            }),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, describe_message)
+            }
+            BytesResult::Invalid(i) => Ok(DescribedValue {
+                value: Value::Bytes(Bytes::from(i)),
+                fields: Vec::new(),
+            }),
+        }
+    }
+    /// Decodes bytes like decode, and also gives a hash of the decoded content. The hash is
+    /// computed over the decoded value, so it doesn't depend on the order of the fields in the
+    /// bytes, and can be used to detect duplicate messages.
+    pub fn decode_and_hash(&mut self, bytes: Option<&[u8]>) -> Result<(Value, u64), SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok((
+                Value::Bytes(Bytes::new()),
+                content_hash(&DecodedValue::Null),
+            )),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, |context, m| {
+                    let hash = content_hash(&to_decoded_value(context, m.clone()));
+                    (Value::Message(Box::from(m)), hash)
+                })
+            }
+            BytesResult::Invalid(i) => {
+                let hash = content_hash(&DecodedValue::Bytes(i.clone()));
+                Ok((Value::Bytes(Bytes::from(i)), hash))
+            }
+        }
+    }
+    /// Decodes bytes like decode, but leaves out the fields that couldn't be parsed, also from
+    /// nested messages. The numbers of those fields are returned together with the value, so as
+    /// much as possible of a partly corrupt message can be inspected. Since decode keeps corrupt
+    /// fields as incomplete values, this is opt-in.
+    pub fn decode_skipping_corrupt_fields(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<(Value, Vec<u64>), SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok((Value::Bytes(Bytes::new()), Vec::new())),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, |_, mut m| {
+                    let corrupt = remove_corrupt_fields(&mut m);
+                    (Value::Message(Box::from(m)), corrupt)
+                })
+            }
+            BytesResult::Invalid(i) => Ok((Value::Bytes(Bytes::from(i)), Vec::new())),
+        }
+    }
+    /// Decodes bytes like decode, but the fields with a number unknown to the schema, for example
+    /// added by a newer version of the schema, are taken out of the message and returned
+    /// separately. This way they can be logged or passed on instead of being overlooked.
+    pub fn decode_with_unknown_fields(
+        &mut self,
+        bytes: Option<&[u8]>,
+    ) -> Result<(Value, Vec<UnknownField>), SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok((Value::Bytes(Bytes::new()), Vec::new())),
+            BytesResult::Valid(id, bytes) => {
+                self.deserialize(id, &self.compression.decompress(bytes)?, |_, mut m| {
+                    let unknown = take_unknown_fields(&mut m);
+                    (Value::Message(Box::from(m)), unknown)
+                })
+            }
+            BytesResult::Invalid(i) => Ok((Value::Bytes(Bytes::from(i)), Vec::new())),
+        }
+    }
     /// The actual deserialization trying to get the id from the bytes to retrieve the schema, and
-    /// using a reader transforms the bytes to a value.
-    fn deserialize(&mut self, id: u32, bytes: &[u8]) -> Result<MessageValue, SRCError> {
+    /// using a reader transforms the bytes to a value, which is mapped using the context.
+    fn deserialize<T>(
+        &mut self,
+        id: u32,
+        bytes: &[u8],
+        map: fn(&Context, MessageValue) -> T,
+    ) -> Result<T, SRCError> {
         match self.get_context(id) {
             Ok(s) => {
                 let (index, data) = to_index_and_data(bytes);
                 let full_name = resolve_name(&s.resolver, &index)?;
                 let message_info = s.context.get_message(full_name).unwrap();
-                Ok(message_info.decode(&data, &s.context))
+                Ok(map(&s.context, message_info.decode(data, &s.context)))
             }
             Err(e) => Err(Clone::clone(e)),
         }
@@ -66,27 +317,65 @@ impl ProtoDecoder {
     /// it into the cache.
     fn get_context(&mut self, id: u32) -> &Result<DecodeContext, SRCError> {
         match self.cache.entry(id) {
-            Entry::Occupied(e) => &*e.into_mut(),
+            Entry::Occupied(e) => {
+                self.counters.hit();
+                &*e.into_mut()
+            }
             Entry::Vacant(e) => {
+                self.counters.miss();
                 let v = match get_schema_by_id_and_type(id, &self.sr_settings, SchemaType::Protobuf)
                 {
-                    Ok(v) => to_resolve_context(&self.sr_settings, v),
+                    Ok(v) => to_resolve_context(&self.sr_settings, &self.preloaded_references, v),
                     Err(e) => Err(e.into_cache()),
                 };
                 &*e.insert(v)
             }
         }
     }
+    #[cfg(all(feature = "avro", feature = "json"))]
+    /// Puts a schema that was already fetched, for example to detect the schema type, in the cache,
+    /// so it's not fetched again when decoding.
+    pub(crate) fn add_to_cache(&mut self, id: u32, registered_schema: RegisteredSchema) {
+        if let Entry::Vacant(e) = self.cache.entry(id) {
+            self.counters.miss();
+            e.insert(to_resolve_context(
+                &self.sr_settings,
+                &self.preloaded_references,
+                registered_schema,
+            ));
+        }
+    }
 }
 
+/// Adds the schema after all its references, a reference that was already included, for example
+/// because two schemas import the same file, is only added once.
 fn add_files(
     sr_settings: &SrSettings,
+    preloaded_references: &HashMap<String, String>,
     registered_schema: RegisteredSchema,
     files: &mut Vec<String>,
+    included: &mut HashSet<String>,
+    path: &[String],
 ) -> Result<(), SRCError> {
     for r in registered_schema.references {
+        if !included.insert(r.name.clone()) {
+            continue;
+        }
+        if let Some(schema) = preloaded_references.get(&r.name) {
+            files.push(schema.clone());
+            continue;
+        }
+        let child_path =
+            extend_reference_path(path, &r.subject, sr_settings.max_reference_depth())?;
         let child_schema = get_referenced_schema(sr_settings, &r)?;
-        add_files(sr_settings, child_schema, files)?;
+        add_files(
+            sr_settings,
+            preloaded_references,
+            child_schema,
+            files,
+            included,
+            &child_path,
+        )?;
     }
     files.push(registered_schema.schema);
     Ok(())
@@ -100,11 +389,19 @@ struct DecodeContext {
 
 fn to_resolve_context(
     sr_settings: &SrSettings,
+    preloaded_references: &HashMap<String, String>,
     registered_schema: RegisteredSchema,
 ) -> Result<DecodeContext, SRCError> {
     let resolver = MessageResolver::new(&registered_schema.schema);
     let mut files = Vec::new();
-    add_files(sr_settings, registered_schema, &mut files)?;
+    add_files(
+        sr_settings,
+        preloaded_references,
+        registered_schema,
+        &mut files,
+        &mut HashSet::new(),
+        &[],
+    )?;
     match Context::parse(&files) {
         Ok(context) => Ok(DecodeContext { resolver, context }),
         Err(e) => Err(SRCError::non_retryable_with_cause(
@@ -121,6 +418,7 @@ mod tests {
 
     use crate::blocking::proto_decoder::ProtoDecoder;
     use crate::blocking::schema_registry::SrSettings;
+    use crate::schema_registry_common::SubjectNameStrategy;
     use test_utils::{
         get_proto_body, get_proto_body_with_reference, get_proto_complex,
         get_proto_complex_proto_test_message, get_proto_complex_references, get_proto_hb_101,
@@ -148,6 +446,64 @@ mod tests {
         assert_eq!(Value::UInt64(101u64), message.fields[0].value)
     }
 
+    #[test]
+    fn test_decode_with_id() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 1))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let (id, _) = decoder.decode_with_id(Some(get_proto_hb_101())).unwrap();
+        let error = decoder.decode_with_id(Some(&[1, 0])).unwrap_err();
+
+        assert_eq!(id, 7);
+        assert_eq!(error.error, "no schema id, because of invalid bytes [1, 0]")
+    }
+
+    #[test]
+    fn test_decode_with_name() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 1))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let (name, _) = decoder.decode_with_name(Some(get_proto_hb_101())).unwrap();
+        let (null_name, _) = decoder.decode_with_name(None).unwrap();
+
+        assert_eq!(name, "nl.openweb.data.Heartbeat");
+        assert!(null_name.is_empty())
+    }
+
+    #[test]
+    fn test_decode_with_subject_latest() {
+        let _m = mock("GET", "/subjects/heartbeat-latest-value/versions/latest")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 94))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let strategy =
+            SubjectNameStrategy::TopicNameStrategy(String::from("heartbeat-latest"), false);
+
+        let heartbeat =
+            decoder.decode_with_subject_latest(&strategy, Some(&[0, 0, 0, 0, 93, 0, 8, 101]));
+
+        let message = match heartbeat {
+            Ok(Value::Message(x)) => *x,
+            Err(e) => panic!("Error: {:?}, while none expected", e),
+            Ok(v) => panic!("Other value: {:?} than expected Message", v),
+        };
+        assert_eq!(Value::UInt64(101u64), message.fields[0].value)
+    }
+
     #[test]
     fn test_decoder_cache() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
@@ -176,6 +532,57 @@ mod tests {
         assert_eq!(Value::UInt64(101u64), message.fields[0].value);
     }
 
+    #[test]
+    fn test_decoder_diamond_references() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body_with_reference(
+                r#"syntax = \"proto3\"; import \"b.proto\"; import \"c.proto\"; package diamond; message Top {uint64 beat = 1; B b = 2; C c = 3;}"#,
+                7,
+                r#"{"name": "b.proto", "subject": "b.proto", "version": 1}, {"name": "c.proto", "subject": "c.proto", "version": 1}"#,
+            ))
+            .create();
+        let _m_b = mock("GET", "/subjects/b.proto/versions/1")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body_with_reference(
+                r#"syntax = \"proto3\"; import \"d.proto\"; package diamond; message B {D d = 1;}"#,
+                8,
+                r#"{"name": "d.proto", "subject": "d.proto", "version": 1}"#,
+            ))
+            .create();
+        let _m_c = mock("GET", "/subjects/c.proto/versions/1")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body_with_reference(
+                r#"syntax = \"proto3\"; import \"d.proto\"; package diamond; message C {D d = 1;}"#,
+                9,
+                r#"{"name": "d.proto", "subject": "d.proto", "version": 1}"#,
+            ))
+            .create();
+        let _m_d = mock("GET", "/subjects/d.proto/versions/1")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(
+                r#"syntax = \"proto3\"; package diamond; message D {string id = 1;}"#,
+                10,
+            ))
+            .expect(1)
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoDecoder::new(sr_settings);
+        let top = decoder.decode(Some(get_proto_hb_101())).unwrap();
+
+        let message = match top {
+            Value::Message(x) => *x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+        assert_eq!(Value::UInt64(101u64), message.fields[0].value);
+        _m_d.assert()
+    }
+
     #[test]
     fn test_decoder_complex() {
         let _m = mock("GET", "/schemas/ids/6?deleted=true")
@@ -211,7 +618,7 @@ mod tests {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let decoder = ProtoDecoder::new(sr_settings);
         assert_eq!(
-            "ProtoDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None }, cache: {} }"
+            "ProtoDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None, max_reference_depth: 64, content_types: {}, deadline: None, retry_statuses: {}, read_only: false, query_params: [], schema_transform: None, retry_policy: None, register_only_new_versions: false }, cache: {}, counters: CacheCounters { hits: 0, misses: 0, evictions: 0 }, compression: None, preloaded_references: {} }"
                 .to_owned(),
             format!("{:?}", decoder)
         )
@@ -6,11 +6,13 @@ use crate::blocking::schema_registry::{
 };
 use crate::error::SRCError;
 use crate::proto_raw_common::{
-    to_bytes, to_bytes_single_message, to_decode_context, DecodeContext, EncodeContext,
+    find_field_raw, to_bytes, to_bytes_single_message, to_decode_context, DecodeContext,
+    EncodeContext,
 };
 use crate::proto_resolver::{resolve_name, to_index_and_data, IndexResolver};
 use crate::schema_registry_common::{
-    get_bytes_result, get_subject, BytesResult, RegisteredSchema, SchemaType, SubjectNameStrategy,
+    get_bytes_result, get_subject, BytesResult, CacheCounters, CacheStats, PayloadCompression,
+    RegisteredSchema, SchemaType, SubjectNameStrategy,
 };
 
 /// Encoder that works by prepending the correct bytes in order to make it valid schema registry
@@ -87,6 +89,8 @@ impl ProtoRawEncoder {
 pub struct ProtoRawDecoder {
     sr_settings: SrSettings,
     cache: HashMap<u32, Result<DecodeContext, SRCError>, RandomState>,
+    counters: CacheCounters,
+    compression: PayloadCompression,
 }
 
 impl ProtoRawDecoder {
@@ -99,25 +103,59 @@ impl ProtoRawDecoder {
         ProtoRawDecoder {
             sr_settings,
             cache: HashMap::new(),
+            counters: CacheCounters::default(),
+            compression: PayloadCompression::None,
         }
     }
+    /// Creates a new decoder like `new`, using the settings set with `SrSettings::set_global`.
+    /// Gives an error when the global settings are not set yet.
+    pub fn from_global() -> Result<ProtoRawDecoder, SRCError> {
+        SrSettings::global().map(ProtoRawDecoder::new)
+    }
     /// Remove al the errors from the cache, you might need to/want to run this when a recoverable
     /// error is met. Errors are also cashed to prevent trying to get schema's that either don't
     /// exist or can't be parsed.
     pub fn remove_errors_from_cache(&mut self) {
+        let before = self.cache.len();
         self.cache.retain(|_, v| v.is_ok());
+        self.counters.evicted(before - self.cache.len());
+    }
+    /// Gives a snapshot of the statistics of the cache, like the number of entries, and how often a
+    /// schema could be taken from the cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.counters.to_stats(self.cache.len())
+    }
+    /// Sets the compression of the bytes after the header, which will be decompressed before
+    /// decoding. By default the bytes are not compressed.
+    pub fn set_compression(&mut self, compression: PayloadCompression) {
+        self.compression = compression;
     }
     /// Reads the bytes to get the name, and gives back the data bytes.
     pub fn decode(&mut self, bytes: Option<&[u8]>) -> Result<Option<RawDecodeResult>, SRCError> {
         match get_bytes_result(bytes) {
             BytesResult::Null => Ok(None),
-            BytesResult::Valid(id, bytes) => Ok(Some(self.deserialize(id, &bytes)?)),
+            BytesResult::Valid(id, bytes) => Ok(Some(
+                self.deserialize(id, &self.compression.decompress(bytes)?)?,
+            )),
             BytesResult::Invalid(i) => Err(SRCError::non_retryable_without_cause(&*format!(
                 "Invalid bytes {:?}",
                 i
             ))),
         }
     }
+    /// Reads the raw bytes of a single field, without decoding the rest of the message. The wire
+    /// format is walked until the first occurrence of the field number. For length delimited
+    /// fields, like strings, bytes and embedded messages, the length prefix is left out.
+    pub fn decode_field_raw(
+        &mut self,
+        bytes: Option<&[u8]>,
+        field_number: u32,
+    ) -> Result<Option<Vec<u8>>, SRCError> {
+        match self.decode(bytes)? {
+            None => Ok(None),
+            Some(r) => find_field_raw(&r.bytes, field_number),
+        }
+    }
     /// The actual deserialization trying to get the id from the bytes to retrieve the schema, and
     /// using a reader transforms the bytes to a value.
     fn deserialize(&mut self, id: u32, bytes: &[u8]) -> Result<RawDecodeResult, SRCError> {
@@ -128,7 +166,7 @@ impl ProtoRawDecoder {
                 Ok(RawDecodeResult {
                     schema: &s.schema,
                     full_name,
-                    bytes: data,
+                    bytes: data.to_vec(),
                 })
             }
             Err(e) => Err(Clone::clone(e)),
@@ -138,8 +176,12 @@ impl ProtoRawDecoder {
     /// it into the cache.
     fn get_context(&mut self, id: u32) -> &Result<DecodeContext, SRCError> {
         match self.cache.entry(id) {
-            Entry::Occupied(e) => &*e.into_mut(),
+            Entry::Occupied(e) => {
+                self.counters.hit();
+                &*e.into_mut()
+            }
             Entry::Vacant(e) => {
+                self.counters.miss();
                 let v = match get_schema_by_id_and_type(id, &self.sr_settings, SchemaType::Protobuf)
                 {
                     Ok(r) => Ok(to_decode_context(r)),
@@ -303,12 +345,8 @@ mod tests {
 
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let mut encoder = ProtoRawEncoder::new(sr_settings);
-        let result_reference = SuppliedReference {
-            name: String::from("result.proto"),
-            subject: String::from("result.proto"),
-            schema: String::from(get_proto_result()),
-            references: vec![],
-        };
+        let result_reference =
+            SuppliedReference::new("result.proto", "result.proto", get_proto_result(), vec![]);
         let supplied_schema = SuppliedSchema {
             name: Some(String::from("test.proto")),
             schema_type: SchemaType::Protobuf,
@@ -334,7 +372,7 @@ mod tests {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let decoder = ProtoRawEncoder::new(sr_settings);
         assert_eq!(
-            "ProtoRawEncoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None }, cache: {} }"
+            "ProtoRawEncoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None, max_reference_depth: 64, content_types: {}, deadline: None, retry_statuses: {}, read_only: false, query_params: [], schema_transform: None, retry_policy: None, register_only_new_versions: false }, cache: {} }"
                 .to_owned(),
             format!("{:?}", decoder)
         )
@@ -417,12 +455,29 @@ mod tests {
         assert_eq!(raw_result.bytes, get_proto_complex_only_data())
     }
 
+    #[test]
+    fn test_decode_field_raw() {
+        let _m = mock("GET", "/schemas/ids/7?deleted=true")
+            .with_status(200)
+            .with_header("content-type", "application/vnd.schemaregistry.v1+json")
+            .with_body(get_proto_body(get_proto_hb_schema(), 7))
+            .create();
+
+        let sr_settings = SrSettings::new(format!("http://{}", server_address()));
+        let mut decoder = ProtoRawDecoder::new(sr_settings);
+        let beat = decoder
+            .decode_field_raw(Some(get_proto_hb_101()), 1)
+            .unwrap();
+
+        assert_eq!(beat, Some(vec![101]))
+    }
+
     #[test]
     fn display_decoder() {
         let sr_settings = SrSettings::new(format!("http://{}", server_address()));
         let decoder = ProtoRawDecoder::new(sr_settings);
         assert_eq!(
-            "ProtoRawDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None }, cache: {} }"
+            "ProtoRawDecoder { sr_settings: SrSettings { urls: [\"http://127.0.0.1:1234\"], client: Client, authorization: None, max_reference_depth: 64, content_types: {}, deadline: None, retry_statuses: {}, read_only: false, query_params: [], schema_transform: None, retry_policy: None, register_only_new_versions: false }, cache: {}, counters: CacheCounters { hits: 0, misses: 0, evictions: 0 }, compression: None }"
                 .to_owned(),
             format!("{:?}", decoder)
         )
@@ -2,14 +2,27 @@ use std::error::Error;
 use std::fmt;
 use std::fmt::Display;
 
+const SCHEMA_NOT_FOUND_ERROR: &str = "schema not found (possibly deleted) in the schema registry";
+const UNKNOWN_MAGIC_BYTE: &str = "no framing known for the magic byte";
+const HTTP_CALL_FAILED: &str = "http call to schema registry failed";
+
 /// Error struct which makes it easy to know if the resulting error is also preserved in the cache
-/// or not. And whether trying it again might not cause an error.
+/// or not. And whether trying it again might not cause an error. The cause is kept as a string, so
+/// the error is Send and Sync, and can be passed between tasks or converted into other errors.
 #[derive(Debug, PartialEq)]
 pub struct SRCError {
     pub error: String,
     pub cause: Option<String>,
     pub retriable: bool,
     pub cached: bool,
+    /// The http status code of the response from the schema registry, when the error is caused by
+    /// an unexpected response.
+    pub status: Option<u16>,
+    /// The error code from the body of the response from the schema registry, like 40401 when the
+    /// subject is not found.
+    pub registry_error_code: Option<i32>,
+    /// The message from the body of the response from the schema registry.
+    pub registry_message: Option<String>,
 }
 
 /// Implements standard error so error handling can be simplified
@@ -23,6 +36,9 @@ impl Clone for SRCError {
             cause: self.cause.as_ref().cloned(),
             retriable: self.retriable,
             cached: self.cached,
+            status: self.status,
+            registry_error_code: self.registry_error_code,
+            registry_message: self.registry_message.as_ref().cloned(),
         }
     }
 }
@@ -54,6 +70,9 @@ impl SRCError {
             cause,
             retriable,
             cached: false,
+            status: None,
+            registry_error_code: None,
+            registry_message: None,
         }
     }
     pub fn retryable_with_cause<T: Display>(cause: T, error: &str) -> SRCError {
@@ -72,6 +91,89 @@ impl SRCError {
             cause: self.cause,
             retriable: self.retriable,
             cached: true,
+            status: self.status,
+            registry_error_code: self.registry_error_code,
+            registry_message: self.registry_message,
+        }
+    }
+    /// Adds the http status code of the response from the schema registry.
+    pub fn with_status(self, status: u16) -> SRCError {
+        SRCError {
+            status: Some(status),
+            ..self
+        }
+    }
+    /// The http status code of the response causing the error, if the error is caused by a
+    /// response. This can be used to handle a 404 different from a 409.
+    pub fn status_code(&self) -> Option<u16> {
+        self.status
+    }
+    /// Adds the error code and message from the body of the response from the schema registry.
+    pub fn with_registry_error(self, error_code: i32, message: &str) -> SRCError {
+        SRCError {
+            registry_error_code: Some(error_code),
+            registry_message: Some(message.to_owned()),
+            ..self
+        }
+    }
+    /// The error code the schema registry gave in the body of the response, if any. This can be
+    /// used to tell a missing subject (40401) apart from a missing version (40402).
+    pub fn registry_error_code(&self) -> Option<i32> {
+        self.registry_error_code
+    }
+    /// Error for a schema id the schema registry doesn't know, not even as soft deleted schema. The
+    /// schema might be permanently deleted, or the id never existed, for example because the bytes
+    /// were produced against another schema registry. Either way retrying won't help.
+    pub fn schema_not_found(id: u32) -> SRCError {
+        SRCError::new(
+            SCHEMA_NOT_FOUND_ERROR,
+            Some(format!("no schema found with id {}", id)),
+            false,
+        )
+        .with_status(404)
+    }
+    /// Whether the error is because the schema was not found (possibly deleted), this can be used to
+    /// handle these messages separately, for example by sending them to another topic.
+    pub fn is_schema_not_found(&self) -> bool {
+        self.error == SCHEMA_NOT_FOUND_ERROR
+    }
+    /// Error for bytes starting with a magic byte there is no framing for, so the id can't be read.
+    pub fn unknown_magic_byte(magic: u8) -> SRCError {
+        SRCError::new(
+            UNKNOWN_MAGIC_BYTE,
+            Some(format!("the first byte is {}", magic)),
+            false,
+        )
+    }
+    /// Whether the error is because of a magic byte there is no framing for.
+    pub fn is_unknown_magic_byte(&self) -> bool {
+        self.error == UNKNOWN_MAGIC_BYTE
+    }
+    /// Error for a call to the schema registry that failed without a response, for example because
+    /// of a timeout, trying again might help.
+    pub fn http_call_failed<T: Display>(cause: T) -> SRCError {
+        SRCError::retryable_with_cause(cause, HTTP_CALL_FAILED)
+    }
+    /// Whether the error is because the call to the schema registry failed without a response.
+    pub fn is_http_call_failed(&self) -> bool {
+        self.error == HTTP_CALL_FAILED
+    }
+    /// Suggests a http status code for the error, for when it needs to be passed on by an http api.
+    /// Errors caused by a response of the schema registry follow its status. A missing subject,
+    /// version or schema, including a permanently deleted one, gives a 404, an incompatible schema
+    /// a 409, and a failing schema registry, or one refusing the credentials, a 502. Failed calls
+    /// also give a 502, other retryable errors a 503. All the other errors are about the supplied
+    /// data and give a 400.
+    pub fn suggested_http_status(&self) -> u16 {
+        match self.status {
+            Some(404) => 404,
+            Some(409) => 409,
+            Some(401) | Some(403) => 502,
+            Some(status) if status >= 500 => 502,
+            Some(_) => 400,
+            None if self.is_http_call_failed() => 502,
+            None if self.retriable => 503,
+            None => 400,
         }
     }
 }
@@ -95,4 +197,80 @@ mod tests {
         );
         assert_eq!(format!("{}", err), "Error: Could not get id from response, was cause by error in response, it\'s retriable: false, it\'s cached: false".to_owned())
     }
+
+    #[test]
+    fn status_code_kept_in_cache() {
+        let err = SRCError::new("schema registry responded with status 409", None, false)
+            .with_status(409)
+            .into_cache();
+        assert_eq!(err.status_code(), Some(409));
+        assert_eq!(err.clone().status_code(), Some(409));
+        assert_eq!(format!("{}", err), "Error: schema registry responded with status 409 had no other cause, it\'s retriable: false, it\'s cached: true".to_owned())
+    }
+
+    #[test]
+    fn schema_not_found() {
+        let err = SRCError::schema_not_found(5).into_cache();
+        assert!(err.is_schema_not_found());
+        assert!(!err.retriable);
+        assert_eq!(err.status_code(), Some(404));
+        assert_eq!(err.suggested_http_status(), 404);
+        assert!(!SRCError::non_retryable_without_cause("other").is_schema_not_found())
+    }
+
+    #[test]
+    fn suggested_http_status_by_error() {
+        let transport = SRCError::http_call_failed("timeout");
+        let not_found = SRCError::new(
+            "schema registry responded with error code 40401",
+            None,
+            false,
+        )
+        .with_status(404)
+        .with_registry_error(40401, "Subject 'heartbeat-value' not found.");
+        let incompatible =
+            SRCError::new("schema registry responded with error code 409", None, false)
+                .with_status(409)
+                .with_registry_error(
+                    409,
+                    "Schema being registered is incompatible with an earlier schema",
+                );
+        let unavailable = SRCError::new("schema registry responded with status 503", None, false)
+            .with_status(503);
+        let invalid = SRCError::new(
+            "schema registry responded with error code 42201",
+            None,
+            false,
+        )
+        .with_status(422)
+        .with_registry_error(42201, "Invalid schema");
+        let parse = SRCError::non_retryable_without_cause("Could not transform bytes using schema");
+        let mentions_incompatible =
+            SRCError::non_retryable_without_cause("value is incompatible with the schema");
+        assert!(transport.is_http_call_failed());
+        assert_eq!(transport.suggested_http_status(), 502);
+        assert_eq!(not_found.suggested_http_status(), 404);
+        assert_eq!(incompatible.suggested_http_status(), 409);
+        assert_eq!(unavailable.suggested_http_status(), 502);
+        assert_eq!(invalid.suggested_http_status(), 400);
+        assert_eq!(parse.suggested_http_status(), 400);
+        assert_eq!(mentions_incompatible.suggested_http_status(), 400);
+        assert_eq!(
+            SRCError::retryable_with_cause("lock", "cache busy").suggested_http_status(),
+            503
+        )
+    }
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn error_is_send_and_sync() {
+        assert_send_sync::<SRCError>();
+        let boxed: Box<dyn std::error::Error + Send + Sync + 'static> =
+            Box::new(SRCError::non_retryable_without_cause("boxed"));
+        assert_eq!(
+            format!("{}", boxed),
+            "Error: boxed had no other cause, it\'s retriable: false, it\'s cached: false"
+        )
+    }
 }
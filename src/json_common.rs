@@ -3,7 +3,7 @@ use url::Url;
 use valico::json_schema::validators::ValidationState;
 
 use crate::error::SRCError;
-use crate::schema_registry_common::get_payload;
+use crate::schema_registry_common::{get_payload, ContentHasher};
 
 pub(crate) fn handle_validation(
     validation: ValidationState,
@@ -68,3 +68,46 @@ pub(crate) fn to_value(str: &str) -> Result<Value, SRCError> {
     };
     Ok(value)
 }
+
+/// Gives a hash of the content of the value. The keys of objects are hashed in sorted order, so the
+/// hash doesn't depend on the order of the keys in the json.
+pub(crate) fn content_hash(value: &Value) -> u64 {
+    let mut hasher = ContentHasher::default();
+    hash_value(&mut hasher, value);
+    hasher.finish()
+}
+
+fn hash_value(hasher: &mut ContentHasher, value: &Value) {
+    match value {
+        Value::Null => hasher.tag(0),
+        Value::Bool(b) => {
+            hasher.tag(1);
+            hasher.write(&[u8::from(*b)])
+        }
+        Value::Number(n) => {
+            hasher.tag(2);
+            hasher.write_sized(n.to_string().as_bytes())
+        }
+        Value::String(s) => {
+            hasher.tag(3);
+            hasher.write_sized(s.as_bytes())
+        }
+        Value::Array(items) => {
+            hasher.tag(4);
+            hasher.write(&(items.len() as u64).to_le_bytes());
+            for item in items {
+                hash_value(hasher, item)
+            }
+        }
+        Value::Object(entries) => {
+            hasher.tag(5);
+            hasher.write(&(entries.len() as u64).to_le_bytes());
+            let mut keys: Vec<&String> = entries.keys().collect();
+            keys.sort();
+            for key in keys {
+                hasher.write_sized(key.as_bytes());
+                hash_value(hasher, &entries[key])
+            }
+        }
+    }
+}
@@ -30,8 +30,10 @@ pub mod blocking;
 pub mod error;
 #[cfg(feature = "json")]
 mod json_common;
+#[cfg(feature = "proto_decoder")]
+pub mod proto_decoder_common;
 #[cfg(feature = "proto_raw")]
 pub mod proto_raw_common;
 #[cfg(any(feature = "proto_decoder", feature = "proto_raw"))]
-mod proto_resolver;
+pub mod proto_resolver;
 pub mod schema_registry_common;
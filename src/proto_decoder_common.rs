@@ -0,0 +1,695 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use protofish::context::{MessageInfo, Multiplicity, ValueType};
+use protofish::decode::{PackedArray, UnknownValue};
+use protofish::{Context, MessageValue, Value};
+use serde_json::Map;
+
+use crate::error::SRCError;
+use crate::proto_resolver::to_index_and_data;
+use crate::schema_registry_common::{
+    get_bytes_result, BytesResult, ContentHasher, DescribedValue, FieldDescriptor,
+};
+
+/// Decoder which doesn't use the schema registry at all. Instead it uses a prebuilt protofish
+/// context, and a map from the schema id to the full name of the message. This way messages can
+/// be decoded without any http calls, as long as the used schema's are known in advance.
+#[derive(Debug)]
+pub struct ContextDecoder {
+    context: Context,
+    names: HashMap<u32, String, RandomState>,
+}
+
+impl ContextDecoder {
+    /// Creates a new decoder, the names should be the fully qualified names of the messages, like
+    /// `nl.openweb.data.Heartbeat`, and be part of the context.
+    pub fn new(context: Context, names: HashMap<u32, String>) -> ContextDecoder {
+        ContextDecoder { context, names }
+    }
+    /// Decodes bytes into a value, in the same way as the ProtoDecoder does. Since the message is
+    /// known from the id, the message indexes in the bytes are ignored.
+    pub fn decode(&self, bytes: Option<&[u8]>) -> Result<Value, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(Value::Bytes(Bytes::new())),
+            BytesResult::Valid(id, bytes) => self.deserialize(id, &bytes),
+            BytesResult::Invalid(i) => Ok(Value::Bytes(Bytes::from(i))),
+        }
+    }
+
+    /// Decodes bytes like decode, but converts the well-known types Timestamp, Duration and Struct
+    /// to more convenient types.
+    pub fn decode_with_well_known_types(
+        &self,
+        bytes: Option<&[u8]>,
+    ) -> Result<ConvertedValue, SRCError> {
+        match self.decode(bytes)? {
+            Value::Message(m) => Ok(convert_well_known_types(&self.context, *m)),
+            v => Ok(ConvertedValue::Other(v)),
+        }
+    }
+
+    /// Decodes bytes like decode, but into a value owned by this crate, so protofish is not needed
+    /// to use the result.
+    pub fn decode_value(&self, bytes: Option<&[u8]>) -> Result<DecodedValue, SRCError> {
+        match get_bytes_result(bytes) {
+            BytesResult::Null => Ok(DecodedValue::Null),
+            BytesResult::Valid(id, bytes) => match self.deserialize(id, &bytes)? {
+                Value::Message(m) => Ok(to_decoded_value(&self.context, *m)),
+                v => Ok(value_to_decoded(&self.context, v)),
+            },
+            BytesResult::Invalid(i) => Ok(DecodedValue::Bytes(i)),
+        }
+    }
+
+    /// Decodes bytes like decode, but leaves out the fields that couldn't be parsed, giving the
+    /// numbers of those fields together with the value.
+    pub fn decode_skipping_corrupt_fields(
+        &self,
+        bytes: Option<&[u8]>,
+    ) -> Result<(Value, Vec<u64>), SRCError> {
+        match self.decode(bytes)? {
+            Value::Message(mut m) => {
+                let corrupt = remove_corrupt_fields(&mut m);
+                Ok((Value::Message(m), corrupt))
+            }
+            v => Ok((v, Vec::new())),
+        }
+    }
+
+    /// Decodes bytes like decode, but returns the fields unknown to the schema separately, so they
+    /// can be logged or passed on.
+    pub fn decode_with_unknown_fields(
+        &self,
+        bytes: Option<&[u8]>,
+    ) -> Result<(Value, Vec<UnknownField>), SRCError> {
+        match self.decode(bytes)? {
+            Value::Message(mut m) => {
+                let unknown = take_unknown_fields(&mut m);
+                Ok((Value::Message(m), unknown))
+            }
+            v => Ok((v, Vec::new())),
+        }
+    }
+
+    fn deserialize(&self, id: u32, bytes: &[u8]) -> Result<Value, SRCError> {
+        let name = match self.names.get(&id) {
+            Some(n) => n,
+            None => {
+                return Err(SRCError::non_retryable_without_cause(&format!(
+                    "No message name known for id {}",
+                    id
+                )))
+            }
+        };
+        let message_info = match self.context.get_message(name) {
+            Some(m) => m,
+            None => {
+                return Err(SRCError::non_retryable_without_cause(&format!(
+                    "Message {} is not part of the context",
+                    name
+                )))
+            }
+        };
+        if bytes.is_empty() {
+            return Err(SRCError::non_retryable_without_cause(
+                "Missing the message indexes",
+            ));
+        }
+        let (_, data) = to_index_and_data(bytes);
+        Ok(Value::Message(Box::from(
+            message_info.decode(data, &self.context),
+        )))
+    }
+}
+
+/// Value where the well-known types of protobuf are converted, other values are kept as is.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConvertedValue {
+    /// A `google.protobuf.Timestamp`.
+    Timestamp(SystemTime),
+    /// A `google.protobuf.Duration`, negative durations are kept as message.
+    Duration(Duration),
+    /// A `google.protobuf.Struct`, `google.protobuf.Value` or `google.protobuf.ListValue`.
+    Struct(serde_json::Value),
+    /// Any other message, with its fields converted.
+    Message(ConvertedMessage),
+    /// Any other value.
+    Other(Value),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConvertedMessage {
+    pub full_name: String,
+    pub fields: Vec<ConvertedField>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConvertedField {
+    pub number: u64,
+    pub value: ConvertedValue,
+}
+
+/// Converts a decoded message, and all messages it contains, replacing Timestamp, Duration and
+/// Struct from the `google.protobuf` package. The context should be the one used for decoding.
+pub fn convert_well_known_types(context: &Context, message: MessageValue) -> ConvertedValue {
+    let full_name = context.resolve_message(message.msg_ref).full_name.clone();
+    let converted = match full_name.as_str() {
+        "google.protobuf.Timestamp" => to_timestamp(&message).map(ConvertedValue::Timestamp),
+        "google.protobuf.Duration" => to_duration(&message).map(ConvertedValue::Duration),
+        "google.protobuf.Struct" => Some(ConvertedValue::Struct(struct_to_json(&message))),
+        "google.protobuf.Value" => Some(ConvertedValue::Struct(value_to_json(&message))),
+        "google.protobuf.ListValue" => Some(ConvertedValue::Struct(list_to_json(&message))),
+        _ => None,
+    };
+    match converted {
+        Some(v) => v,
+        None => ConvertedValue::Message(ConvertedMessage {
+            full_name,
+            fields: message
+                .fields
+                .into_iter()
+                .map(|f| ConvertedField {
+                    number: f.number,
+                    value: match f.value {
+                        Value::Message(m) => convert_well_known_types(context, *m),
+                        v => ConvertedValue::Other(v),
+                    },
+                })
+                .collect(),
+        }),
+    }
+}
+
+fn seconds_and_nanos(message: &MessageValue) -> (i64, i32) {
+    let mut seconds = 0;
+    let mut nanos = 0;
+    for field in &message.fields {
+        match (field.number, &field.value) {
+            (1, Value::Int64(s)) => seconds = *s,
+            (2, Value::Int32(n)) => nanos = *n,
+            _ => (),
+        }
+    }
+    (seconds, nanos)
+}
+
+fn to_timestamp(message: &MessageValue) -> Option<SystemTime> {
+    let (seconds, nanos) = seconds_and_nanos(message);
+    let nanos = Duration::from_nanos(nanos.max(0) as u64);
+    if seconds >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64) + nanos)
+    } else {
+        UNIX_EPOCH
+            .checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+            .and_then(|t| t.checked_add(nanos))
+    }
+}
+
+fn to_duration(message: &MessageValue) -> Option<Duration> {
+    match seconds_and_nanos(message) {
+        (s, n) if s >= 0 && n >= 0 => Some(Duration::new(s as u64, n as u32)),
+        _ => None,
+    }
+}
+
+fn struct_to_json(message: &MessageValue) -> serde_json::Value {
+    let mut map = Map::new();
+    for field in &message.fields {
+        if let (1, Value::Message(entry)) = (field.number, &field.value) {
+            let mut key = String::new();
+            let mut value = serde_json::Value::Null;
+            for entry_field in &entry.fields {
+                match (entry_field.number, &entry_field.value) {
+                    (1, Value::String(k)) => key = k.clone(),
+                    (2, Value::Message(v)) => value = value_to_json(v),
+                    _ => (),
+                }
+            }
+            map.insert(key, value);
+        }
+    }
+    serde_json::Value::Object(map)
+}
+
+fn value_to_json(message: &MessageValue) -> serde_json::Value {
+    for field in &message.fields {
+        match (field.number, &field.value) {
+            (2, Value::Double(d)) => return serde_json::json!(d),
+            (3, Value::String(s)) => return serde_json::Value::String(s.clone()),
+            (4, Value::Bool(b)) => return serde_json::Value::Bool(*b),
+            (5, Value::Message(m)) => return struct_to_json(m),
+            (6, Value::Message(m)) => return list_to_json(m),
+            _ => (),
+        }
+    }
+    serde_json::Value::Null
+}
+
+fn list_to_json(message: &MessageValue) -> serde_json::Value {
+    serde_json::Value::Array(
+        message
+            .fields
+            .iter()
+            .filter_map(|f| match (f.number, &f.value) {
+                (1, Value::Message(m)) => Some(value_to_json(m)),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+/// Value independent of the protobuf library used for decoding. Messages become maps with the names
+/// of the fields as keys, repeated fields become lists, and enums the name of the enum value. All
+/// integers become an Int, except for uint64 values that don't fit, which become a Float.
+#[derive(Debug, PartialEq, Clone)]
+pub enum DecodedValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<DecodedValue>),
+    Map(BTreeMap<String, DecodedValue>),
+}
+
+/// Converts a decoded message into a map, fields not known in the context are left out. The context
+/// should be the one used for decoding.
+pub fn to_decoded_value(context: &Context, message: MessageValue) -> DecodedValue {
+    let message_info = context.resolve_message(message.msg_ref);
+    let mut map = BTreeMap::new();
+    for field in message.fields {
+        let field_info = match message_info.fields.get(&field.number) {
+            Some(f) => f,
+            None => continue,
+        };
+        let mut values = match field.value {
+            Value::Packed(p) => packed_to_decoded(p),
+            v => vec![value_to_decoded(context, v)],
+        };
+        if let Multiplicity::Single = field_info.multiplicity {
+            if let Some(v) = values.pop() {
+                map.insert(field_info.name.clone(), v);
+            }
+        } else if let DecodedValue::List(list) = map
+            .entry(field_info.name.clone())
+            .or_insert_with(|| DecodedValue::List(Vec::new()))
+        {
+            list.append(&mut values)
+        }
+    }
+    DecodedValue::Map(map)
+}
+
+/// Removes the fields that couldn't be parsed, also from nested messages, and gives the numbers of
+/// the removed fields. Protofish doesn't fail on a corrupt field, but keeps the bytes as an
+/// incomplete or invalid value, which makes the other fields still available.
+pub fn remove_corrupt_fields(message: &mut MessageValue) -> Vec<u64> {
+    let mut corrupt = Vec::new();
+    message.fields.retain(|f| {
+        let keep = !matches!(
+            f.value,
+            Value::Incomplete(_) | Value::Unknown(UnknownValue::Invalid(_))
+        );
+        if !keep {
+            corrupt.push(f.number);
+        }
+        keep
+    });
+    for field in message.fields.iter_mut() {
+        if let Value::Message(m) = &mut field.value {
+            corrupt.append(&mut remove_corrupt_fields(m));
+        }
+    }
+    corrupt
+}
+
+/// A field with a number the schema doesn't know about, typically added by a newer version of the
+/// schema. The value keeps the raw content, bytes for length delimited fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownField {
+    pub number: u64,
+    pub value: UnknownValue,
+}
+
+/// Removes the fields with a number unknown to the schema from the message and returns them, in
+/// the order they appeared. Only the fields of the message itself are taken, unknown fields of
+/// nested messages stay part of the nested message. Corrupt fields are not taken, for those see
+/// `remove_corrupt_fields`.
+pub fn take_unknown_fields(message: &mut MessageValue) -> Vec<UnknownField> {
+    let mut unknown = Vec::new();
+    message.fields.retain(|f| match &f.value {
+        Value::Unknown(v) if !matches!(v, UnknownValue::Invalid(_)) => {
+            unknown.push(UnknownField {
+                number: f.number,
+                value: v.clone(),
+            });
+            false
+        }
+        _ => true,
+    });
+    unknown
+}
+
+/// Gives the decoded message together with the descriptors of its fields, the type names are the
+/// ones used in the proto file, with the full name for messages and enums.
+pub fn describe_message(context: &Context, message: MessageValue) -> DescribedValue<Value> {
+    let fields = message_fields(context, context.resolve_message(message.msg_ref));
+    DescribedValue {
+        value: Value::Message(Box::from(message)),
+        fields,
+    }
+}
+
+/// Parses the proto file and gives the descriptors of the fields of the message with the full name.
+pub(crate) fn describe_proto_fields(
+    schema: &str,
+    full_name: &str,
+) -> Result<Vec<FieldDescriptor>, SRCError> {
+    let context = match Context::parse([schema]) {
+        Ok(c) => c,
+        Err(e) => {
+            return Err(SRCError::non_retryable_with_cause(
+                e,
+                "Could not parse the Protobuf schema",
+            ))
+        }
+    };
+    match context.get_message(full_name) {
+        Some(m) => Ok(message_fields(&context, m)),
+        None => Err(SRCError::non_retryable_without_cause(&format!(
+            "Could not find message {} in the Protobuf schema",
+            full_name
+        ))),
+    }
+}
+
+fn message_fields(context: &Context, message: &MessageInfo) -> Vec<FieldDescriptor> {
+    message
+        .fields
+        .values()
+        .map(|f| FieldDescriptor {
+            name: f.name.clone(),
+            type_name: proto_type_name(context, &f.field_type),
+            nullable: matches!(f.field_type, ValueType::Message(_)),
+            repeated: !matches!(f.multiplicity, Multiplicity::Single),
+        })
+        .collect()
+}
+
+fn proto_type_name(context: &Context, value_type: &ValueType) -> String {
+    let name = match value_type {
+        ValueType::Double => "double",
+        ValueType::Float => "float",
+        ValueType::Int32 => "int32",
+        ValueType::Int64 => "int64",
+        ValueType::UInt32 => "uint32",
+        ValueType::UInt64 => "uint64",
+        ValueType::SInt32 => "sint32",
+        ValueType::SInt64 => "sint64",
+        ValueType::Fixed32 => "fixed32",
+        ValueType::Fixed64 => "fixed64",
+        ValueType::SFixed32 => "sfixed32",
+        ValueType::SFixed64 => "sfixed64",
+        ValueType::Bool => "bool",
+        ValueType::String => "string",
+        ValueType::Bytes => "bytes",
+        ValueType::Message(m) => return context.resolve_message(*m).full_name.clone(),
+        ValueType::Enum(e) => return context.resolve_enum(*e).full_name.clone(),
+    };
+    String::from(name)
+}
+
+/// Gives a hash of the content of the decoded value, since maps are ordered by key the hash doesn't
+/// depend on the order of the fields in the bytes.
+pub(crate) fn content_hash(value: &DecodedValue) -> u64 {
+    let mut hasher = ContentHasher::default();
+    hash_decoded(&mut hasher, value);
+    hasher.finish()
+}
+
+fn hash_decoded(hasher: &mut ContentHasher, value: &DecodedValue) {
+    match value {
+        DecodedValue::Null => hasher.tag(0),
+        DecodedValue::Bool(b) => {
+            hasher.tag(1);
+            hasher.write(&[u8::from(*b)])
+        }
+        DecodedValue::Int(i) => {
+            hasher.tag(2);
+            hasher.write(&i.to_le_bytes())
+        }
+        DecodedValue::Float(f) => {
+            hasher.tag(3);
+            hasher.write(&f.to_bits().to_le_bytes())
+        }
+        DecodedValue::String(s) => {
+            hasher.tag(4);
+            hasher.write_sized(s.as_bytes())
+        }
+        DecodedValue::Bytes(b) => {
+            hasher.tag(5);
+            hasher.write_sized(b)
+        }
+        DecodedValue::List(items) => {
+            hasher.tag(6);
+            hasher.write(&(items.len() as u64).to_le_bytes());
+            for item in items {
+                hash_decoded(hasher, item)
+            }
+        }
+        DecodedValue::Map(entries) => {
+            hasher.tag(7);
+            hasher.write(&(entries.len() as u64).to_le_bytes());
+            for (key, v) in entries {
+                hasher.write_sized(key.as_bytes());
+                hash_decoded(hasher, v)
+            }
+        }
+    }
+}
+
+fn uint_to_decoded(value: u64) -> DecodedValue {
+    match i64::try_from(value) {
+        Ok(v) => DecodedValue::Int(v),
+        Err(_) => DecodedValue::Float(value as f64),
+    }
+}
+
+fn value_to_decoded(context: &Context, value: Value) -> DecodedValue {
+    match value {
+        Value::Double(v) => DecodedValue::Float(v),
+        Value::Float(v) => DecodedValue::Float(f64::from(v)),
+        Value::Int32(v) | Value::SInt32(v) | Value::SFixed32(v) => DecodedValue::Int(i64::from(v)),
+        Value::Int64(v) | Value::SInt64(v) | Value::SFixed64(v) => DecodedValue::Int(v),
+        Value::UInt32(v) | Value::Fixed32(v) => DecodedValue::Int(i64::from(v)),
+        Value::UInt64(v) | Value::Fixed64(v) => uint_to_decoded(v),
+        Value::Bool(v) => DecodedValue::Bool(v),
+        Value::String(v) => DecodedValue::String(v),
+        Value::Bytes(v) | Value::Incomplete(v) => DecodedValue::Bytes(v.to_vec()),
+        Value::Packed(p) => DecodedValue::List(packed_to_decoded(p)),
+        Value::Message(m) => to_decoded_value(context, *m),
+        Value::Enum(e) => match context.resolve_enum(e.enum_ref).field_by_value(e.value) {
+            Some(f) => DecodedValue::String(f.name.clone()),
+            None => DecodedValue::Int(e.value),
+        },
+        Value::Unknown(_) => DecodedValue::Null,
+    }
+}
+
+fn packed_to_decoded(packed: PackedArray) -> Vec<DecodedValue> {
+    fn ints<T: Into<i64>>(values: Vec<T>) -> Vec<DecodedValue> {
+        values
+            .into_iter()
+            .map(|v| DecodedValue::Int(v.into()))
+            .collect()
+    }
+    match packed {
+        PackedArray::Double(v) => v.into_iter().map(DecodedValue::Float).collect(),
+        PackedArray::Float(v) => v
+            .into_iter()
+            .map(|f| DecodedValue::Float(f64::from(f)))
+            .collect(),
+        PackedArray::Int32(v) | PackedArray::SInt32(v) | PackedArray::SFixed32(v) => ints(v),
+        PackedArray::Int64(v) | PackedArray::SInt64(v) | PackedArray::SFixed64(v) => ints(v),
+        PackedArray::UInt32(v) | PackedArray::Fixed32(v) => ints(v),
+        PackedArray::UInt64(v) | PackedArray::Fixed64(v) => {
+            v.into_iter().map(uint_to_decoded).collect()
+        }
+        PackedArray::Bool(v) => v.into_iter().map(DecodedValue::Bool).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use bytes::Bytes;
+    use protofish::decode::UnknownValue;
+    use protofish::{Context, Value};
+
+    use crate::proto_decoder_common::{
+        ContextDecoder, ConvertedField, ConvertedValue, DecodedValue, UnknownField,
+    };
+    use test_utils::{get_proto_hb_101, get_proto_hb_schema};
+
+    fn get_decoder(id: u32) -> ContextDecoder {
+        let schema = get_proto_hb_schema().replace("\\\"", "\"");
+        let context = Context::parse(&[schema]).unwrap();
+        let mut names = HashMap::new();
+        names.insert(id, String::from("nl.openweb.data.Heartbeat"));
+        ContextDecoder::new(context, names)
+    }
+
+    #[test]
+    fn decode_with_context() {
+        let decoder = get_decoder(7);
+        let heartbeat = decoder.decode(Some(get_proto_hb_101())).unwrap();
+
+        let message = match heartbeat {
+            Value::Message(x) => *x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+
+        assert_eq!(Value::UInt64(101u64), message.fields[0].value)
+    }
+
+    #[test]
+    fn decode_with_unknown_fields_with_context() {
+        let decoder = get_decoder(7);
+        let bytes: &[u8] = &[0, 0, 0, 0, 7, 0, 8, 101, 18, 1, 65, 24, 5];
+
+        let (heartbeat, unknown) = decoder.decode_with_unknown_fields(Some(bytes)).unwrap();
+
+        let message = match heartbeat {
+            Value::Message(x) => *x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+        assert_eq!(message.fields.len(), 1);
+        assert_eq!(Value::UInt64(101u64), message.fields[0].value);
+        assert_eq!(
+            unknown,
+            vec![
+                UnknownField {
+                    number: 2,
+                    value: UnknownValue::VariableLength(Bytes::from(vec![65]))
+                },
+                UnknownField {
+                    number: 3,
+                    value: UnknownValue::Varint(5)
+                }
+            ]
+        )
+    }
+
+    #[test]
+    fn decode_skipping_corrupt_fields_with_context() {
+        let schema = r#"syntax = "proto3"; package nl.openweb.data; message Heartbeat {uint64 beat = 1; string note = 2;}"#;
+        let context = Context::parse([schema]).unwrap();
+        let mut names = HashMap::new();
+        names.insert(7, String::from("nl.openweb.data.Heartbeat"));
+        let decoder = ContextDecoder::new(context, names);
+        let bytes: &[u8] = &[0, 0, 0, 0, 7, 0, 8, 101, 18, 10, 65];
+
+        let (heartbeat, corrupt) = decoder.decode_skipping_corrupt_fields(Some(bytes)).unwrap();
+
+        let message = match heartbeat {
+            Value::Message(x) => *x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+        assert_eq!(message.fields.len(), 1);
+        assert_eq!(Value::UInt64(101u64), message.fields[0].value);
+        assert_eq!(corrupt, vec![2]);
+        match decoder.decode(Some(bytes)).unwrap() {
+            Value::Message(x) => assert_eq!(x.fields.len(), 2),
+            v => panic!("Other value: {:?} than expected Message", v),
+        }
+    }
+
+    #[test]
+    fn decode_value_with_context() {
+        let decoder = get_decoder(7);
+        let heartbeat = decoder.decode_value(Some(get_proto_hb_101())).unwrap();
+
+        let mut expected = BTreeMap::new();
+        expected.insert(String::from("beat"), DecodedValue::Int(101));
+        assert_eq!(heartbeat, DecodedValue::Map(expected));
+        assert_eq!(decoder.decode_value(None).unwrap(), DecodedValue::Null)
+    }
+
+    #[test]
+    fn decode_with_context_unknown_id() {
+        let decoder = get_decoder(8);
+        let error = decoder.decode(Some(get_proto_hb_101())).unwrap_err();
+
+        assert_eq!(error.error, "No message name known for id 7")
+    }
+
+    fn get_well_known_decoder() -> ContextDecoder {
+        let well_known = "syntax = \"proto3\"; package google.protobuf; message Timestamp {int64 seconds = 1; int32 nanos = 2;} message Duration {int64 seconds = 1; int32 nanos = 2;} message Struct {repeated FieldsEntry fields = 1; message FieldsEntry {string key = 1; Value value = 2;}} message Value {oneof kind {NullValue null_value = 1; double number_value = 2; string string_value = 3; bool bool_value = 4; Struct struct_value = 5; ListValue list_value = 6;}} enum NullValue {NULL_VALUE = 0;} message ListValue {repeated Value values = 1;}";
+        let event = "syntax = \"proto3\"; package nl.openweb.data; message Event {google.protobuf.Timestamp at = 1; google.protobuf.Duration took = 2; google.protobuf.Struct details = 3; uint64 beat = 4;}";
+        let context = Context::parse([well_known, event]).unwrap();
+        let mut names = HashMap::new();
+        names.insert(7, String::from("nl.openweb.data.Event"));
+        ContextDecoder::new(context, names)
+    }
+
+    #[test]
+    fn decode_with_well_known_types() {
+        let decoder = get_well_known_decoder();
+        let bytes = [
+            0, 0, 0, 0, 7, 0, 10, 9, 8, 128, 160, 248, 250, 5, 16, 244, 3, 18, 2, 8, 90, 26, 9, 10,
+            7, 10, 1, 97, 18, 2, 32, 1, 32, 101,
+        ];
+        let event = decoder.decode_with_well_known_types(Some(&bytes)).unwrap();
+
+        let message = match event {
+            ConvertedValue::Message(x) => x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+
+        assert_eq!(message.full_name, "nl.openweb.data.Event");
+        let values: Vec<ConvertedValue> = message.fields.into_iter().map(|f| f.value).collect();
+        assert_eq!(
+            values,
+            vec![
+                ConvertedValue::Timestamp(UNIX_EPOCH + Duration::new(1_600_000_000, 500)),
+                ConvertedValue::Duration(Duration::from_secs(90)),
+                ConvertedValue::Struct(serde_json::json!({"a": true})),
+                ConvertedValue::Other(Value::UInt64(101u64)),
+            ]
+        )
+    }
+
+    #[test]
+    fn negative_duration_kept_as_message() {
+        let decoder = get_well_known_decoder();
+        let bytes = [
+            0, 0, 0, 0, 7, 0, 18, 11, 8, 246, 255, 255, 255, 255, 255, 255, 255, 255, 1,
+        ];
+        let event = decoder.decode_with_well_known_types(Some(&bytes)).unwrap();
+
+        let message = match event {
+            ConvertedValue::Message(x) => x,
+            v => panic!("Other value: {:?} than expected Message", v),
+        };
+        let duration = match &message.fields[0] {
+            ConvertedField {
+                number: 2,
+                value: ConvertedValue::Message(d),
+            } => d,
+            f => panic!("Other field: {:?} than expected Duration message", f),
+        };
+
+        assert_eq!(duration.full_name, "google.protobuf.Duration");
+        assert_eq!(
+            duration.fields[0].value,
+            ConvertedValue::Other(Value::Int64(-10))
+        )
+    }
+}
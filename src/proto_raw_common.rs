@@ -1,5 +1,7 @@
+use std::convert::TryFrom;
+
 use crate::error::SRCError;
-use crate::proto_resolver::{IndexResolver, MessageResolver};
+use crate::proto_resolver::{write_message_index, IndexResolver, MessageResolver};
 use crate::schema_registry_common::{get_payload, RegisteredSchema};
 use integer_encoding::VarInt;
 
@@ -9,14 +11,7 @@ pub(crate) fn to_bytes(
     full_name: &str,
 ) -> Result<Vec<u8>, SRCError> {
     let mut index_bytes = match encode_context.resolver.find_index(full_name) {
-        Some(v) if v.len() == 1 && v[0] == 0i32 => vec![0u8],
-        Some(v) => {
-            let mut result = (v.len() as i32).encode_var_vec();
-            for i in v {
-                result.append(&mut i.encode_var_vec())
-            }
-            result
-        }
+        Some(v) => write_message_index(v),
         None => {
             return Err(SRCError::non_retryable_without_cause(&*format!(
                 "could not find name {} with resolver",
@@ -45,6 +40,63 @@ pub(crate) fn to_bytes_single_message(
     }
 }
 
+/// Walks the protobuf wire format to find the first occurrence of the field with the given number,
+/// without decoding any of the other fields. For length delimited fields the length prefix is
+/// stripped, for the other wire types the bytes are returned as they are on the wire.
+pub(crate) fn find_field_raw(data: &[u8], field_number: u32) -> Result<Option<Vec<u8>>, SRCError> {
+    let mut position = 0;
+    while position < data.len() {
+        let (key, key_size) = read_varint(&data[position..])?;
+        position += key_size;
+        let (start, end) = match key & 0x7 {
+            0 => {
+                let (_, size) = read_varint(&data[position..])?;
+                (position, position.checked_add(size))
+            }
+            1 => (position, position.checked_add(8)),
+            2 => {
+                let (length, size) = read_varint(&data[position..])?;
+                let start = position + size;
+                let end = usize::try_from(length)
+                    .ok()
+                    .and_then(|l| start.checked_add(l));
+                (start, end)
+            }
+            5 => (position, position.checked_add(4)),
+            w => {
+                return Err(SRCError::non_retryable_without_cause(&format!(
+                    "unsupported wire type {} for field {}",
+                    w,
+                    key >> 3
+                )))
+            }
+        };
+        let end = match end {
+            Some(e) if e <= data.len() => e,
+            _ => {
+                return Err(SRCError::non_retryable_without_cause(&format!(
+                    "field {} is truncated",
+                    key >> 3
+                )))
+            }
+        };
+        if key >> 3 == u64::from(field_number) {
+            return Ok(Some(data[start..end].to_vec()));
+        }
+        position = end;
+    }
+    Ok(None)
+}
+
+fn read_varint(bytes: &[u8]) -> Result<(u64, usize), SRCError> {
+    match u64::decode_var(bytes) {
+        Some(v) => Ok(v),
+        None => Err(SRCError::non_retryable_without_cause(
+            "could not read varint from bytes",
+        )),
+    }
+}
+
 pub(crate) fn to_decode_context(registered_schema: RegisteredSchema) -> DecodeContext {
     let schema = String::from(&registered_schema.schema);
     DecodeContext {
@@ -64,3 +116,63 @@ pub(crate) struct DecodeContext {
     pub(crate) schema: RegisteredSchema,
     pub(crate) resolver: MessageResolver,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::proto_raw_common::{find_field_raw, to_bytes, EncodeContext};
+    use crate::proto_resolver::IndexResolver;
+    use test_utils::{get_proto_complex, get_proto_complex_only_data};
+
+    #[test]
+    fn to_bytes_writes_message_index() {
+        let encode_context = EncodeContext {
+            id: 6,
+            resolver: IndexResolver::new(get_proto_complex()),
+        };
+
+        let first = to_bytes(
+            &encode_context,
+            &[8, 1],
+            "org.schema_registry_test_app.proto.A",
+        );
+        let nested = to_bytes(
+            &encode_context,
+            &[8, 1],
+            "org.schema_registry_test_app.proto.C.D",
+        );
+
+        assert_eq!(first, Ok(vec![0, 0, 0, 0, 6, 0x00, 8, 1]));
+        assert_eq!(nested, Ok(vec![0, 0, 0, 0, 6, 4, 4, 0, 8, 1]))
+    }
+
+    #[test]
+    fn find_field_raw_length_delimited() {
+        let result = find_field_raw(get_proto_complex_only_data(), 4).unwrap();
+        assert_eq!(result, Some(b"String".to_vec()))
+    }
+
+    #[test]
+    fn find_field_raw_varint() {
+        let result = find_field_raw(get_proto_complex_only_data(), 3).unwrap();
+        assert_eq!(result, Some(vec![1]))
+    }
+
+    #[test]
+    fn find_field_raw_not_present() {
+        let result = find_field_raw(get_proto_complex_only_data(), 9).unwrap();
+        assert_eq!(result, None)
+    }
+
+    #[test]
+    fn find_field_raw_truncated() {
+        let result = find_field_raw(&[34, 6, 83, 116], 4).unwrap_err();
+        assert_eq!(result.error, "field 4 is truncated")
+    }
+
+    #[test]
+    fn find_field_raw_huge_length() {
+        let data = [34, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1, 83];
+        let result = find_field_raw(&data, 4).unwrap_err();
+        assert_eq!(result.error, "field 4 is truncated")
+    }
+}
@@ -1,9 +1,11 @@
+//! Resolves the message of a protobuf schema from the message index in the bytes, and the other
+//! way around. The functions to read and write the message index are public, so the framing can
+//! also be used outside the decoders and encoders.
 use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
-use std::io::BufReader;
 
 use crate::error::SRCError;
-use integer_encoding::VarIntReader;
+use integer_encoding::{VarInt, VarIntReader};
 use logos::Logos;
 
 #[derive(Debug, Clone)]
@@ -160,17 +162,60 @@ fn same_vec(first: &[i32], second: &[i32]) -> bool {
     true
 }
 
-pub(crate) fn to_index_and_data(bytes: &[u8]) -> (Vec<i32>, Vec<u8>) {
+/// Writes the message index as used in the Confluent protobuf framing. The index of the first
+/// message, which is the most common one, is written as a single zero byte.
+pub fn write_message_index(indexes: &[i32]) -> Vec<u8> {
+    if indexes.is_empty() || indexes == [0] {
+        return vec![0u8];
+    }
+    let mut result = (indexes.len() as i32).encode_var_vec();
+    for i in indexes {
+        result.append(&mut i.encode_var_vec())
+    }
+    result
+}
+
+/// Reads the message index at the start of the bytes, returning the index together with the
+/// number of bytes it took, so the remaining bytes are the encoded message.
+pub fn read_message_index(bytes: &[u8]) -> Result<(Vec<i32>, usize), SRCError> {
+    match bytes.first() {
+        None => Err(SRCError::non_retryable_without_cause(
+            "no bytes to read the message index from",
+        )),
+        Some(0) => Ok((vec![0], 1)),
+        Some(_) => {
+            let mut reader = bytes;
+            let count: i32 = reader.read_varint().map_err(|e| {
+                SRCError::non_retryable_with_cause(e, "could not read message index")
+            })?;
+            if count < 0 {
+                return Err(SRCError::non_retryable_without_cause(&format!(
+                    "invalid message index length {}",
+                    count
+                )));
+            }
+            let mut index = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                index.push(reader.read_varint().map_err(|e| {
+                    SRCError::non_retryable_with_cause(e, "could not read message index")
+                })?)
+            }
+            Ok((index, bytes.len() - reader.len()))
+        }
+    }
+}
+
+pub(crate) fn to_index_and_data(bytes: &[u8]) -> (Vec<i32>, &[u8]) {
     if bytes[0] == 0 {
-        (vec![0], bytes[1..].to_vec())
+        (vec![0], &bytes[1..])
     } else {
-        let mut reader = BufReader::new(bytes);
+        let mut reader = bytes;
         let count: i32 = reader.read_varint().unwrap();
         let mut index = Vec::new();
         for _ in 0..count {
             index.push(reader.read_varint().unwrap())
         }
-        (index, reader.buffer().to_vec())
+        (index, reader)
     }
 }
 
@@ -187,9 +232,22 @@ pub(crate) fn resolve_name<'a>(
     }
 }
 
+/// Returns the full name of the first message in the proto file, which is the one used when there
+/// is no other way to determine the message.
+pub(crate) fn extract_proto_name(s: &str) -> Option<String> {
+    MessageResolver::new(s).find_name(&[0]).cloned()
+}
+
+/// Returns the package of the proto file, if there is one.
+pub(crate) fn extract_proto_package(s: &str) -> Option<String> {
+    ResolverHelper::new(s).package
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::proto_resolver::{IndexResolver, MessageResolver};
+    use crate::proto_resolver::{
+        read_message_index, to_index_and_data, write_message_index, IndexResolver, MessageResolver,
+    };
 
     fn get_proto_simple() -> &'static str {
         r#"syntax = "proto3";package nl.openweb.data; message Heartbeat{uint64 beat = 1;}"#
@@ -258,4 +316,33 @@ mod tests {
             Some(&vec![3])
         );
     }
+
+    #[test]
+    fn test_index_and_data_larger_than_buffer() {
+        let mut bytes = vec![4, 4, 0];
+        bytes.extend(vec![1u8; 10000]);
+
+        let (index, data) = to_index_and_data(&bytes);
+
+        assert_eq!(index, vec![2, 0]);
+        assert_eq!(data.len(), 10000)
+    }
+
+    #[test]
+    fn message_index_round_trip() {
+        assert_eq!(write_message_index(&[0]), vec![0]);
+        assert_eq!(write_message_index(&[]), vec![0]);
+        assert_eq!(read_message_index(&[0, 8, 101]), Ok((vec![0], 1)));
+
+        let bytes = write_message_index(&[2, 0, 70]);
+        assert_eq!(bytes, vec![6, 4, 0, 140, 1]);
+        assert_eq!(read_message_index(&bytes), Ok((vec![2, 0, 70], 5)));
+    }
+
+    #[test]
+    fn read_message_index_invalid() {
+        assert!(read_message_index(&[]).is_err());
+        assert!(read_message_index(&[4, 2]).is_err());
+        assert!(read_message_index(&[1]).is_err())
+    }
 }